@@ -0,0 +1,691 @@
+use sevenz_rust::{Password, SevenZReader};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+use zip::read::ZipArchive;
+
+use super::format::ArchiveFormat;
+use super::metadata::EntryMetadata;
+use super::safety::{
+    EntryInfo, EntryKind, SafeEntry, SafetyLimits, copy_entry, validate_entries, validate_zip,
+};
+
+/// 7z 条目属性中表示高 16 位为 Unix 权限的标志
+const SEVEN_ZIP_UNIX_EXTENSION: u32 = 0x8000;
+/// `FILE_ATTRIBUTE_REPARSE_POINT`，Windows 下的符号链接与联接点
+const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// 解压时去除的顶层目录层数
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StripComponents {
+    /// 去除固定层数，层数不足的条目被跳过
+    Count(usize),
+    /// 所有条目位于同一个顶层目录下时去除该目录（例如 `STranslate_x.y.z/`）
+    Auto,
+}
+
+impl Default for StripComponents {
+    fn default() -> Self {
+        StripComponents::Count(0)
+    }
+}
+
+impl FromStr for StripComponents {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(StripComponents::Auto);
+        }
+        s.parse()
+            .map(StripComponents::Count)
+            .map_err(|_| format!("应为非负整数或 auto: {}", s))
+    }
+}
+
+impl fmt::Display for StripComponents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StripComponents::Count(count) => write!(f, "{}", count),
+            StripComponents::Auto => f.write_str("auto"),
+        }
+    }
+}
+
+/// 识别压缩包格式，校验全部条目后解压到目标目录，返回识别出的格式
+pub fn extract_archive(
+    archive: &Path,
+    destination: &Path,
+    limits: &SafetyLimits,
+    strip: StripComponents,
+    verbose: bool,
+) -> Result<ArchiveFormat, Box<dyn Error>> {
+    let format = ArchiveFormat::detect(archive)?;
+    if verbose {
+        println!("   压缩包格式: {}", format);
+    }
+
+    match format {
+        ArchiveFormat::Zip => extract_zip(archive, destination, limits, strip, verbose)?,
+        ArchiveFormat::TarGz | ArchiveFormat::TarZst => {
+            extract_tar(archive, format, destination, limits, strip, verbose)?
+        }
+        ArchiveFormat::SevenZip => extract_7z(archive, destination, limits, strip, verbose)?,
+        ArchiveFormat::Directory => copy_dir(archive, destination, limits, strip, verbose)?,
+    }
+    Ok(format)
+}
+
+/// 校验压缩包中的全部条目但不解压，用于解压前估算所需空间
+pub fn list_archive(
+    archive: &Path,
+    limits: &SafetyLimits,
+) -> Result<Vec<SafeEntry>, Box<dyn Error>> {
+    match ArchiveFormat::detect(archive)? {
+        ArchiveFormat::Zip => validate_zip(&mut ZipArchive::new(File::open(archive)?)?, limits),
+        format @ (ArchiveFormat::TarGz | ArchiveFormat::TarZst) => {
+            validate_tar(archive, format, limits)
+        }
+        ArchiveFormat::SevenZip => Ok(validate_7z(&open_7z(archive)?, archive, limits)?.0),
+        ArchiveFormat::Directory => Ok(validate_dir(archive, limits)?.0),
+    }
+}
+
+fn extract_zip(
+    path: &Path,
+    destination: &Path,
+    limits: &SafetyLimits,
+    strip: StripComponents,
+    verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let entries = strip_entries(validate_zip(&mut archive, limits)?, strip)?;
+
+    let mut progress = Progress::new(&entries, verbose);
+    for entry in &entries {
+        let mut reader = archive.by_index(entry.index)?;
+        write_entry(entry, &mut reader, destination)?;
+        progress.advance(entry);
+    }
+    apply_dir_metadata(&entries, destination)?;
+    Ok(())
+}
+
+/// tar 只能顺序读取：第一遍只读条目头完成校验，第二遍再解压通过校验的条目
+fn extract_tar(
+    path: &Path,
+    format: ArchiveFormat,
+    destination: &Path,
+    limits: &SafetyLimits,
+    strip: StripComponents,
+    verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    let entries = validate_tar(path, format, limits)?;
+    let entries: HashMap<usize, SafeEntry> = strip_entries(entries, strip)?
+        .into_iter()
+        .map(|entry| (entry.index, entry))
+        .collect();
+    let last = entries.keys().max().copied();
+
+    let mut progress = Progress::new(entries.values(), verbose);
+    for_each_tar_entry(path, format, |index, entry| {
+        if let Some(safe) = entries.get(&index) {
+            write_entry(safe, entry, destination)?;
+            progress.advance(safe);
+        }
+        Ok(Some(index) < last)
+    })?;
+    apply_dir_metadata(entries.values(), destination)?;
+    Ok(())
+}
+
+/// 只读取 tar 条目头完成校验，不写入任何文件
+fn validate_tar(
+    path: &Path,
+    format: ArchiveFormat,
+    limits: &SafetyLimits,
+) -> Result<Vec<SafeEntry>, Box<dyn Error>> {
+    let mut infos = Vec::new();
+    let mut total_size: u64 = 0;
+    for_each_tar_entry(path, format, |_, entry| {
+        let header = entry.header().entry_type();
+        let kind = if header.is_file() || header.is_contiguous() {
+            EntryKind::File
+        } else if header.is_dir() {
+            EntryKind::Dir
+        } else if header.is_symlink() {
+            EntryKind::Symlink
+        } else {
+            EntryKind::Special
+        };
+        let size = entry.size();
+        let header = entry.header();
+        let metadata = EntryMetadata::from_unix(
+            header
+                .mtime()
+                .ok()
+                .and_then(|mtime| i64::try_from(mtime).ok()),
+            header.mode().ok(),
+        );
+        infos.push(EntryInfo {
+            name: String::from_utf8_lossy(&entry.path_bytes()).into_owned(),
+            kind,
+            size,
+            compressed_size: None,
+            metadata,
+        });
+
+        // 超出限制后不再继续读取，避免为校验而解压整个压缩炸弹
+        total_size = total_size.saturating_add(size);
+        Ok(infos.len() <= limits.max_entries && total_size <= limits.max_total_size)
+    })?;
+
+    let packed_size = fs::metadata(path)?.len();
+    validate_entries(infos, Some(packed_size), limits)
+}
+
+/// 依次读取 tar 条目（跳过 pax 全局头），回调返回 `false` 时停止
+fn for_each_tar_entry(
+    path: &Path,
+    format: ArchiveFormat,
+    mut each: impl FnMut(usize, &mut tar::Entry<'_, Box<dyn Read>>) -> Result<bool, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let file = BufReader::new(File::open(path)?);
+    let reader: Box<dyn Read> = match format {
+        ArchiveFormat::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+        ArchiveFormat::TarZst => {
+            let mut decoder = zstd::stream::read::Decoder::with_buffer(file)?;
+            decoder.window_log_max(31)?;
+            Box::new(decoder)
+        }
+        _ => unreachable!("非 tar 格式"),
+    };
+
+    let mut archive = tar::Archive::new(reader);
+    let mut index = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_pax_global_extensions() {
+            continue;
+        }
+        if !each(index, &mut entry)? {
+            break;
+        }
+        index += 1;
+    }
+    Ok(())
+}
+
+fn extract_7z(
+    path: &Path,
+    destination: &Path,
+    limits: &SafetyLimits,
+    strip: StripComponents,
+    verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut reader = open_7z(path)?;
+
+    let (entries, names) = validate_7z(&reader, path, limits)?;
+    let entries: HashMap<String, SafeEntry> = strip_entries(entries, strip)?
+        .into_iter()
+        .map(|entry| (names[entry.index].clone(), entry))
+        .collect();
+
+    // 7z 按压缩块顺序解出条目，与文件列表顺序不同，因此按名称对应
+    let mut progress = Progress::new(entries.values(), verbose);
+    reader
+        .for_each_entries(|file, data| {
+            if let Some(safe) = entries.get(file.name()) {
+                write_entry(safe, data, destination)?;
+                progress.advance(safe);
+            } else {
+                // 固实压缩块中的条目共用一个数据流，跳过的条目也要读完，后续条目才能对齐
+                io::copy(data, &mut io::sink())?;
+            }
+            Ok(true)
+        })
+        .map_err(|e| format!("解压 7z 压缩包失败: {}", e))?;
+    apply_dir_metadata(entries.values(), destination)?;
+    Ok(())
+}
+
+fn open_7z(path: &Path) -> Result<SevenZReader<File>, Box<dyn Error>> {
+    SevenZReader::open(path, Password::empty())
+        .map_err(|e| format!("读取 7z 压缩包失败: {}", e).into())
+}
+
+/// 根据 7z 文件头中的条目列表完成校验，同时返回各条目的原始名称（与 `SafeEntry::index` 对应）
+fn validate_7z(
+    reader: &SevenZReader<File>,
+    path: &Path,
+    limits: &SafetyLimits,
+) -> Result<(Vec<SafeEntry>, Vec<String>), Box<dyn Error>> {
+    let files = &reader.archive().files;
+    // 反项（anti-item）是增量归档中的删除标记，不对应任何内容，解压时忽略
+    let infos: Vec<EntryInfo> = files
+        .iter()
+        .filter(|file| !file.is_anti_item())
+        .map(|file| {
+            let attributes = file.windows_attributes();
+            let is_symlink = file.has_windows_attributes
+                && (attributes & FILE_ATTRIBUTE_REPARSE_POINT != 0
+                    || (attributes & SEVEN_ZIP_UNIX_EXTENSION != 0
+                        && (attributes >> 16) & S_IFMT == S_IFLNK));
+            let kind = if is_symlink {
+                EntryKind::Symlink
+            } else if file.is_directory() {
+                EntryKind::Dir
+            } else {
+                EntryKind::File
+            };
+            let metadata = EntryMetadata {
+                modified: file
+                    .has_last_modified_date
+                    .then(|| SystemTime::from(file.last_modified_date())),
+                mode: (file.has_windows_attributes && attributes & SEVEN_ZIP_UNIX_EXTENSION != 0)
+                    .then_some(attributes >> 16),
+            };
+            EntryInfo {
+                name: file.name().to_string(),
+                kind,
+                size: file.size(),
+                compressed_size: None,
+                metadata,
+            }
+        })
+        .collect();
+    let names: Vec<String> = infos.iter().map(|info| info.name.clone()).collect();
+
+    let packed_size = fs::metadata(path)?.len();
+    Ok((validate_entries(infos, Some(packed_size), limits)?, names))
+}
+
+/// 目录形式的更新包按与压缩包相同的规则校验后复制，符号链接等特殊条目同样被拒绝
+fn copy_dir(
+    path: &Path,
+    destination: &Path,
+    limits: &SafetyLimits,
+    strip: StripComponents,
+    verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (entries, sources) = validate_dir(path, limits)?;
+    let entries = strip_entries(entries, strip)?;
+
+    let mut progress = Progress::new(&entries, verbose);
+    for entry in &entries {
+        if entry.is_dir {
+            write_entry(entry, &mut io::empty(), destination)?;
+        } else {
+            let mut reader = File::open(&sources[entry.index])?;
+            write_entry(entry, &mut reader, destination)?;
+        }
+        progress.advance(entry);
+    }
+    apply_dir_metadata(&entries, destination)?;
+    Ok(())
+}
+
+/// 遍历目录生成条目列表并校验，同时返回各条目的源路径（与 `SafeEntry::index` 对应）
+fn validate_dir(
+    root: &Path,
+    limits: &SafetyLimits,
+) -> Result<(Vec<SafeEntry>, Vec<PathBuf>), Box<dyn Error>> {
+    let mut infos = Vec::new();
+    let mut sources = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let mut children: Vec<_> =
+            fs::read_dir(root.join(&relative))?.collect::<io::Result<_>>()?;
+        children.sort_by_key(|entry| entry.file_name());
+        for child in children {
+            let child_relative = relative.join(child.file_name());
+            let metadata = fs::symlink_metadata(child.path())?;
+            let kind = if metadata.is_symlink() {
+                EntryKind::Symlink
+            } else if metadata.is_dir() {
+                pending.push(child_relative.clone());
+                EntryKind::Dir
+            } else if metadata.is_file() {
+                EntryKind::File
+            } else {
+                EntryKind::Special
+            };
+
+            #[cfg(unix)]
+            let mode = Some(std::os::unix::fs::PermissionsExt::mode(
+                &metadata.permissions(),
+            ));
+            #[cfg(not(unix))]
+            let mode = None;
+
+            let name = child_relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            infos.push(EntryInfo {
+                name,
+                kind,
+                size: if kind == EntryKind::File {
+                    metadata.len()
+                } else {
+                    0
+                },
+                compressed_size: None,
+                metadata: EntryMetadata {
+                    modified: metadata.modified().ok(),
+                    mode,
+                },
+            });
+            sources.push(root.join(&child_relative));
+        }
+    }
+    Ok((validate_entries(infos, None, limits)?, sources))
+}
+
+/// 去除条目路径开头的目录层级；去除后路径为空的条目被跳过，不同条目映射到同一路径时报错
+fn strip_entries(
+    entries: Vec<SafeEntry>,
+    strip: StripComponents,
+) -> Result<Vec<SafeEntry>, Box<dyn Error>> {
+    let count = match strip {
+        StripComponents::Count(0) => return Ok(entries),
+        StripComponents::Count(count) => count,
+        StripComponents::Auto => match common_root(&entries) {
+            Some(root) => {
+                println!("📂 检测到顶层目录 {}/，解压时将其去除", root.display());
+                1
+            }
+            None => return Ok(entries),
+        },
+    };
+
+    let mut seen = HashSet::new();
+    let mut stripped = Vec::with_capacity(entries.len());
+    for mut entry in entries {
+        let path: PathBuf = entry.path.components().skip(count).collect();
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        if !seen.insert(path.clone()) && !entry.is_dir {
+            return Err(format!("去除 {} 层目录后路径冲突: {}", count, path.display()).into());
+        }
+        entry.path = path;
+        stripped.push(entry);
+    }
+    Ok(stripped)
+}
+
+/// 所有条目都位于同一个顶层目录下时返回该目录
+fn common_root(entries: &[SafeEntry]) -> Option<PathBuf> {
+    let mut root = None;
+    for entry in entries {
+        let mut components = entry.path.components();
+        let first = components.next()?;
+        // 顶层存在文件时说明压缩包内容已在根目录
+        if components.next().is_none() && !entry.is_dir {
+            return None;
+        }
+        match &root {
+            None => root = Some(first),
+            Some(existing) if *existing != first => return None,
+            Some(_) => {}
+        }
+    }
+    root.map(|component| PathBuf::from(component.as_os_str()))
+}
+
+/// 将通过校验的条目写入目标目录
+fn write_entry<R: Read + ?Sized>(
+    entry: &SafeEntry,
+    reader: &mut R,
+    destination: &Path,
+) -> io::Result<()> {
+    let outpath = destination.join(&entry.path);
+    if entry.is_dir {
+        return fs::create_dir_all(&outpath);
+    }
+
+    if let Some(parent) = outpath.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut outfile = File::create(&outpath)?;
+    copy_entry(reader, &mut outfile, entry.size)?;
+    entry.metadata.apply_to_file(&outfile, &outpath)
+}
+
+/// 所有文件写入后再还原目录的修改时间与权限，避免被随后写入的内容覆盖
+fn apply_dir_metadata<'a>(
+    entries: impl IntoIterator<Item = &'a SafeEntry>,
+    destination: &Path,
+) -> io::Result<()> {
+    for entry in entries.into_iter().filter(|entry| entry.is_dir) {
+        entry
+            .metadata
+            .apply_to_dir(&destination.join(&entry.path))?;
+    }
+    Ok(())
+}
+
+/// 解压进度，开启详细输出时按解压字节数每增加 10% 输出一次
+struct Progress {
+    verbose: bool,
+    total_files: usize,
+    total_bytes: u64,
+    files: usize,
+    bytes: u64,
+    next_percent: u64,
+}
+
+impl Progress {
+    fn new<'a>(entries: impl IntoIterator<Item = &'a SafeEntry>, verbose: bool) -> Self {
+        let (total_files, total_bytes) = entries
+            .into_iter()
+            .filter(|entry| !entry.is_dir)
+            .fold((0, 0u64), |(files, bytes), entry| {
+                (files + 1, bytes.saturating_add(entry.size))
+            });
+        Self {
+            verbose,
+            total_files,
+            total_bytes,
+            files: 0,
+            bytes: 0,
+            next_percent: 10,
+        }
+    }
+
+    fn advance(&mut self, entry: &SafeEntry) {
+        if entry.is_dir {
+            return;
+        }
+        self.files += 1;
+        self.bytes = self.bytes.saturating_add(entry.size);
+        if !self.verbose {
+            return;
+        }
+
+        let percent = match self.total_bytes {
+            0 => 100,
+            total => self.bytes.saturating_mul(100) / total,
+        };
+        if percent >= self.next_percent
+            || (self.files == self.total_files && self.next_percent <= 100)
+        {
+            println!(
+                "   解压进度: {}% ({}/{} 个文件)",
+                percent, self.files, self.total_files
+            );
+            self.next_percent = (percent / 10 + 1) * 10;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, is_dir: bool) -> SafeEntry {
+        SafeEntry {
+            index: 0,
+            path: PathBuf::from(path),
+            is_dir,
+            size: 0,
+            metadata: Default::default(),
+        }
+    }
+
+    fn paths(entries: &[SafeEntry]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|entry| entry.path.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn common_root_requires_single_top_level_directory() {
+        let nested = [
+            entry("STranslate_2.0.0", true),
+            entry("STranslate_2.0.0/STranslate.exe", false),
+            entry("STranslate_2.0.0/Plugins/a.dll", false),
+        ];
+        assert_eq!(
+            common_root(&nested),
+            Some(PathBuf::from("STranslate_2.0.0"))
+        );
+
+        let file_at_root = [
+            entry("STranslate.exe", false),
+            entry("Plugins/a.dll", false),
+        ];
+        assert_eq!(common_root(&file_at_root), None);
+
+        let two_roots = [entry("a/x.dll", false), entry("b/y.dll", false)];
+        assert_eq!(common_root(&two_roots), None);
+    }
+
+    #[test]
+    fn auto_strip_removes_common_root() {
+        let entries = vec![
+            entry("STranslate_2.0.0", true),
+            entry("STranslate_2.0.0/STranslate.exe", false),
+            entry("STranslate_2.0.0/Plugins/a.dll", false),
+        ];
+        let stripped = strip_entries(entries, StripComponents::Auto).unwrap();
+        assert_eq!(paths(&stripped), ["STranslate.exe", "Plugins/a.dll"]);
+    }
+
+    #[test]
+    fn auto_strip_keeps_plugin_only_package() {
+        let entries = vec![
+            entry("Plugins", true),
+            entry("Plugins/X", true),
+            entry("Plugins/X/plugin.json", false),
+        ];
+        // 唯一的顶层目录同样会被去除，因此附加更新包不能使用 auto
+        let stripped = strip_entries(entries.clone(), StripComponents::Auto).unwrap();
+        assert_eq!(paths(&stripped), ["X", "X/plugin.json"]);
+        let kept = strip_entries(entries, StripComponents::default()).unwrap();
+        assert_eq!(
+            paths(&kept),
+            ["Plugins", "Plugins/X", "Plugins/X/plugin.json"]
+        );
+    }
+
+    #[test]
+    fn count_strip_skips_shallow_entries_and_detects_collisions() {
+        let entries = vec![
+            entry("top.txt", false),
+            entry("a/b/c.txt", false),
+            entry("a/d.txt", false),
+        ];
+        let stripped = strip_entries(entries, StripComponents::Count(1)).unwrap();
+        assert_eq!(paths(&stripped), ["b/c.txt", "d.txt"]);
+
+        let colliding = vec![entry("a/x.txt", false), entry("b/x.txt", false)];
+        assert!(strip_entries(colliding, StripComponents::Count(1)).is_err());
+    }
+
+    #[test]
+    fn parses_strip_components() {
+        assert!(matches!(
+            "auto".parse::<StripComponents>(),
+            Ok(StripComponents::Auto)
+        ));
+        assert!(matches!(
+            "2".parse::<StripComponents>(),
+            Ok(StripComponents::Count(2))
+        ));
+        assert!("-1".parse::<StripComponents>().is_err());
+    }
+
+    /// 生成固实 7z：所有文件写入同一个压缩块，解压时共用一个数据流
+    fn solid_7z(path: &Path, files: &[(&str, &[u8])]) {
+        use sevenz_rust::{SevenZArchiveEntry, SevenZWriter, SourceReader};
+
+        let mut writer = SevenZWriter::create(path).unwrap();
+        let entries = files
+            .iter()
+            .map(|(name, _)| {
+                let mut entry = SevenZArchiveEntry::new();
+                entry.name = name.to_string();
+                entry.has_stream = true;
+                entry
+            })
+            .collect();
+        let readers: Vec<SourceReader<&[u8]>> = files
+            .iter()
+            .map(|(_, data)| SourceReader::new(*data))
+            .collect();
+        writer
+            .push_archive_entries(entries, readers.into())
+            .unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn solid_7z_keeps_entries_after_skipped_ones() {
+        let root = tempfile::tempdir().unwrap();
+        let archive = root.path().join("package.7z");
+        let junk = b"junk from Finder".repeat(64);
+        let top = b"dropped by strip-components".repeat(64);
+        solid_7z(
+            &archive,
+            &[
+                (".DS_Store", &junk),
+                ("top.txt", &top),
+                ("app/keep.txt", b"kept after skipped entries"),
+                ("app/also.txt", b"second kept entry"),
+            ],
+        );
+
+        let destination = root.path().join("out");
+        fs::create_dir_all(&destination).unwrap();
+        extract_7z(
+            &archive,
+            &destination,
+            &SafetyLimits::default(),
+            StripComponents::Count(1),
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read(destination.join("keep.txt")).unwrap(),
+            b"kept after skipped entries"
+        );
+        assert_eq!(
+            fs::read(destination.join("also.txt")).unwrap(),
+            b"second kept entry"
+        );
+        assert!(!destination.join("top.txt").exists());
+        assert!(!destination.join(".DS_Store").exists());
+    }
+}
@@ -0,0 +1,68 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+/// 不含任何条目的 ZIP 只有中央目录结束记录
+const ZIP_EMPTY_MAGIC: &[u8] = b"PK\x05\x06";
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+const SEVEN_ZIP_MAGIC: &[u8] = &[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C];
+
+/// 支持的压缩包格式，按文件头识别，与扩展名无关
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    /// gzip 压缩的 tar
+    TarGz,
+    /// zstd 压缩的 tar
+    TarZst,
+    SevenZip,
+    /// 已解压的目录
+    Directory,
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarZst => "tar.zst",
+            ArchiveFormat::SevenZip => "7z",
+            ArchiveFormat::Directory => "目录",
+        };
+        f.write_str(name)
+    }
+}
+
+impl ArchiveFormat {
+    /// 读取文件头识别压缩包格式，目录视为已解压的更新包
+    pub fn detect(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if path.is_dir() {
+            return Ok(ArchiveFormat::Directory);
+        }
+        let mut header = [0u8; 6];
+        let mut file =
+            File::open(path).map_err(|e| format!("打开压缩包失败: {} ({})", path.display(), e))?;
+        let len = file.read(&mut header)?;
+        let header = &header[..len];
+
+        if header.starts_with(ZIP_MAGIC) || header.starts_with(ZIP_EMPTY_MAGIC) {
+            Ok(ArchiveFormat::Zip)
+        } else if header.starts_with(GZIP_MAGIC) {
+            Ok(ArchiveFormat::TarGz)
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Ok(ArchiveFormat::TarZst)
+        } else if header.starts_with(SEVEN_ZIP_MAGIC) {
+            Ok(ArchiveFormat::SevenZip)
+        } else {
+            Err(format!(
+                "不支持的压缩包格式（仅支持 zip、tar.gz、tar.zst、7z）: {}",
+                path.display()
+            )
+            .into())
+        }
+    }
+}
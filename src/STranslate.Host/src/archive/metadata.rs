@@ -0,0 +1,306 @@
+use chrono::{Datelike, Local, NaiveDate, TimeZone, Timelike};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zip::DateTime;
+use zip::read::ZipFile;
+use zip::write::FileOptions;
+
+/// ZIP 扩展时间戳（"UT"）额外字段的标识
+const EXTENDED_TIMESTAMP_ID: u16 = 0x5455;
+/// 扩展时间戳标志位：包含修改时间
+const EXTENDED_TIMESTAMP_MTIME: u8 = 0x01;
+
+/// 只还原读写执行权限位，不还原 setuid/setgid/sticky
+#[cfg(unix)]
+const PERMISSION_BITS: u32 = 0o777;
+
+/// 条目的修改时间与 Unix 权限，压缩包未记录时为 `None`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EntryMetadata {
+    pub modified: Option<SystemTime>,
+    /// Unix 权限，Windows 下不使用
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub mode: Option<u32>,
+}
+
+impl EntryMetadata {
+    /// 读取 ZIP 条目的元数据，优先使用扩展时间戳（UTC，秒级），其次使用 DOS 时间（本地时间，2 秒精度）
+    pub fn from_zip(file: &ZipFile) -> Self {
+        let modified =
+            extended_timestamp(file.extra_data()).or_else(|| from_dos_time(file.last_modified()));
+        Self {
+            modified,
+            mode: file.unix_mode(),
+        }
+    }
+
+    /// 由秒级 Unix 时间戳构造，用于 tar 等格式
+    pub fn from_unix(mtime: Option<i64>, mode: Option<u32>) -> Self {
+        Self {
+            modified: mtime.map(unix_time),
+            mode,
+        }
+    }
+
+    /// 将元数据写回已解压的文件；文件句柄仍打开时可以直接设置时间
+    pub fn apply_to_file(&self, file: &File, path: &Path) -> io::Result<()> {
+        if let Some(modified) = self.modified {
+            file.set_modified(modified)?;
+        }
+        self.apply_mode(path)
+    }
+
+    /// 将元数据写回目录，应在目录中的内容全部写入后调用，否则修改时间会被覆盖
+    pub fn apply_to_dir(&self, path: &Path) -> io::Result<()> {
+        if let Some(modified) = self.modified {
+            open_for_attributes(path)?.set_modified(modified)?;
+        }
+        self.apply_mode(path)
+    }
+
+    #[cfg(unix)]
+    fn apply_mode(&self, path: &Path) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        match self.mode {
+            Some(mode) => {
+                fs::set_permissions(path, fs::Permissions::from_mode(mode & PERMISSION_BITS))
+            }
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn apply_mode(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 根据文件元数据生成写入 ZIP 时使用的选项（DOS 时间与 Unix 权限）
+pub fn zip_options(metadata: &fs::Metadata, options: FileOptions) -> FileOptions {
+    let mut options = options;
+    if let Some(time) = metadata.modified().ok().and_then(to_dos_time) {
+        options = options.last_modified_time(time);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        options = options.unix_permissions(metadata.permissions().mode() & PERMISSION_BITS);
+    }
+    options
+}
+
+/// 生成只包含修改时间的扩展时间戳额外字段，弥补 DOS 时间的时区与精度问题
+pub fn extended_timestamp_field(metadata: &fs::Metadata) -> Option<Vec<u8>> {
+    let modified = metadata.modified().ok()?;
+    let seconds = match modified.duration_since(UNIX_EPOCH) {
+        Ok(duration) => i32::try_from(duration.as_secs()).ok()?,
+        Err(e) => i32::try_from(e.duration().as_secs()).ok().map(|s| -s)?,
+    };
+
+    let mut field = Vec::with_capacity(9);
+    field.extend_from_slice(&EXTENDED_TIMESTAMP_ID.to_le_bytes());
+    field.extend_from_slice(&5u16.to_le_bytes());
+    field.push(EXTENDED_TIMESTAMP_MTIME);
+    field.extend_from_slice(&seconds.to_le_bytes());
+    Some(field)
+}
+
+/// 在额外字段中查找扩展时间戳并取出修改时间
+fn extended_timestamp(mut extra: &[u8]) -> Option<SystemTime> {
+    while extra.len() >= 4 {
+        let id = u16::from_le_bytes([extra[0], extra[1]]);
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + len)?;
+        if id == EXTENDED_TIMESTAMP_ID {
+            if data.len() >= 5 && data[0] & EXTENDED_TIMESTAMP_MTIME != 0 {
+                let seconds = i32::from_le_bytes([data[1], data[2], data[3], data[4]]);
+                return Some(unix_time(seconds.into()));
+            }
+            return None;
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+fn unix_time(seconds: i64) -> SystemTime {
+    match u64::try_from(seconds) {
+        Ok(seconds) => UNIX_EPOCH + Duration::from_secs(seconds),
+        Err(_) => UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs()),
+    }
+}
+
+fn from_dos_time(time: DateTime) -> Option<SystemTime> {
+    let naive =
+        NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())?
+            .and_hms_opt(
+                time.hour().into(),
+                time.minute().into(),
+                time.second().into(),
+            )?;
+    let local = Local.from_local_datetime(&naive).earliest()?;
+    Some(local.into())
+}
+
+fn to_dos_time(time: SystemTime) -> Option<DateTime> {
+    let local: chrono::DateTime<Local> = time.into();
+    DateTime::from_date_and_time(
+        u16::try_from(local.year()).ok()?,
+        local.month() as u8,
+        local.day() as u8,
+        local.hour() as u8,
+        local.minute() as u8,
+        local.second() as u8,
+    )
+    .ok()
+}
+
+/// 打开文件或目录以修改其属性；Windows 下打开目录需要 `FILE_FLAG_BACKUP_SEMANTICS`
+fn open_for_attributes(path: &Path) -> io::Result<File> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        use winapi::um::winbase::FILE_FLAG_BACKUP_SEMANTICS;
+        fs::OpenOptions::new()
+            .write(true)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+            .open(path)
+    }
+
+    #[cfg(not(windows))]
+    {
+        File::open(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::{ZipArchive, ZipWriter};
+
+    /// 奇数秒，便于确认 DOS 时间的 2 秒精度
+    const MODIFIED: u64 = 1_700_000_001;
+
+    fn file_modified_at(dir: &Path, seconds: u64) -> fs::Metadata {
+        let path = dir.join("file.txt");
+        let file = File::create(&path).unwrap();
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+        file.metadata().unwrap()
+    }
+
+    #[test]
+    fn extended_timestamp_round_trips() {
+        let temp = tempfile::tempdir().unwrap();
+        let metadata = file_modified_at(temp.path(), MODIFIED);
+        let field = extended_timestamp_field(&metadata).unwrap();
+
+        // 扩展时间戳之前的其他额外字段被跳过
+        let mut extra = vec![0x01, 0x00, 0x02, 0x00, 0xaa, 0xbb];
+        extra.extend_from_slice(&field);
+        assert_eq!(
+            extended_timestamp(&extra),
+            Some(UNIX_EPOCH + Duration::from_secs(MODIFIED))
+        );
+    }
+
+    #[test]
+    fn malformed_extended_timestamps_are_ignored() {
+        // 未设置修改时间标志
+        assert_eq!(
+            extended_timestamp(&[0x55, 0x54, 0x05, 0x00, 0x02, 1, 2, 3, 4]),
+            None
+        );
+        // 声明的长度超出额外字段
+        assert_eq!(
+            extended_timestamp(&[0x55, 0x54, 0x09, 0x00, 0x01, 1, 2]),
+            None
+        );
+        assert_eq!(extended_timestamp(&[]), None);
+    }
+
+    #[test]
+    fn negative_unix_times_are_before_the_epoch() {
+        assert_eq!(unix_time(-60), UNIX_EPOCH - Duration::from_secs(60));
+        assert_eq!(
+            EntryMetadata::from_unix(Some(60), None).modified,
+            Some(UNIX_EPOCH + Duration::from_secs(60))
+        );
+        assert_eq!(EntryMetadata::from_unix(None, None).modified, None);
+    }
+
+    #[test]
+    fn dos_time_round_trips_through_local_time() {
+        let time = UNIX_EPOCH + Duration::from_secs(MODIFIED);
+        let dos = to_dos_time(time).unwrap();
+        // DOS 时间按本地时间记录，写入压缩包时才截断为 2 秒精度
+        assert_eq!(from_dos_time(dos), Some(time));
+        // DOS 时间无法表示 1980 年之前
+        assert!(to_dos_time(UNIX_EPOCH).is_none());
+    }
+
+    #[test]
+    fn zip_entries_prefer_the_extended_timestamp() {
+        let temp = tempfile::tempdir().unwrap();
+        let metadata = file_modified_at(temp.path(), MODIFIED);
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip_options(&metadata, FileOptions::default());
+        zip.start_file_with_extra_data("extended.txt", options)
+            .unwrap();
+        zip.write_all(&extended_timestamp_field(&metadata).unwrap())
+            .unwrap();
+        zip.end_extra_data().unwrap();
+        zip.start_file("dos.txt", options).unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let modified = |archive: &mut ZipArchive<_>, name: &str| {
+            EntryMetadata::from_zip(&archive.by_name(name).unwrap()).modified
+        };
+        let exact = UNIX_EPOCH + Duration::from_secs(MODIFIED);
+        assert_eq!(modified(&mut archive, "extended.txt"), Some(exact));
+        // 没有扩展时间戳时使用 2 秒精度的 DOS 时间
+        assert_eq!(
+            modified(&mut archive, "dos.txt"),
+            Some(exact - Duration::from_secs(1))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn apply_sets_time_and_strips_special_mode_bits() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("tool");
+        let file = File::create(&path).unwrap();
+        let dir = temp.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+
+        let metadata = EntryMetadata::from_unix(Some(MODIFIED as i64), Some(0o4751));
+        metadata.apply_to_file(&file, &path).unwrap();
+        metadata.apply_to_dir(&dir).unwrap();
+
+        for path in [&path, &dir] {
+            let actual = fs::metadata(path).unwrap();
+            assert_eq!(actual.permissions().mode() & 0o7777, 0o751);
+            assert_eq!(
+                actual.modified().unwrap(),
+                UNIX_EPOCH + Duration::from_secs(MODIFIED)
+            );
+        }
+
+        // 未记录权限时保持原样
+        EntryMetadata::default()
+            .apply_to_file(&file, &path)
+            .unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o751
+        );
+    }
+}
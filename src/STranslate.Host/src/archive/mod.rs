@@ -0,0 +1,4 @@
+pub mod extract;
+pub mod format;
+pub mod metadata;
+pub mod safety;
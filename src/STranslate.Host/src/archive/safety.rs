@@ -0,0 +1,344 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Seek, Write};
+use std::path::PathBuf;
+use zip::read::ZipArchive;
+
+use super::metadata::EntryMetadata;
+
+/// 解压前需要跳过的系统垃圾文件或目录
+const JUNK_SEGMENTS: [&str; 2] = ["__MACOSX", ".DS_Store"];
+
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// 压缩包安全限制，用于防御压缩炸弹
+#[derive(Clone, Debug)]
+pub struct SafetyLimits {
+    /// 解压后总大小上限（字节）
+    pub max_total_size: u64,
+    /// 条目数量上限
+    pub max_entries: usize,
+    /// 单个条目压缩比上限
+    pub max_ratio: u64,
+}
+
+impl Default for SafetyLimits {
+    fn default() -> Self {
+        Self {
+            max_total_size: 4 * 1024 * 1024 * 1024,
+            max_entries: 100_000,
+            max_ratio: 200,
+        }
+    }
+}
+
+/// 压缩比检查只对超过该大小的条目生效，避免小文本文件误报
+const RATIO_CHECK_THRESHOLD: u64 = 1024 * 1024;
+
+/// 通过校验、可以安全解压的条目
+#[derive(Clone, Debug)]
+pub struct SafeEntry {
+    /// 在压缩包中的索引
+    pub index: usize,
+    /// 规范化后的相对路径，只包含普通路径段
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// 解压后大小
+    pub size: u64,
+    /// 修改时间与 Unix 权限
+    pub metadata: EntryMetadata,
+}
+
+/// 不安全条目的具体原因
+#[derive(Debug)]
+pub enum Violation {
+    AbsolutePath,
+    ParentSegment,
+    DrivePrefix,
+    Backslash,
+    Symlink,
+    SpecialEntry,
+    RatioTooHigh { ratio: u64 },
+    TooManyEntries { count: usize },
+    TotalSizeTooLarge { size: u64 },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::AbsolutePath => write!(f, "绝对路径"),
+            Violation::ParentSegment => write!(f, "包含 .. 路径段"),
+            Violation::DrivePrefix => write!(f, "包含盘符前缀"),
+            Violation::Backslash => write!(f, "使用反斜杠分隔路径"),
+            Violation::Symlink => write!(f, "符号链接"),
+            Violation::SpecialEntry => write!(f, "不支持的条目类型（硬链接或设备文件）"),
+            Violation::RatioTooHigh { ratio } => write!(f, "压缩比过高 ({}:1)", ratio),
+            Violation::TooManyEntries { count } => write!(f, "条目数量过多 ({})", count),
+            Violation::TotalSizeTooLarge { size } => {
+                write!(f, "解压后总大小过大 ({} 字节)", size)
+            }
+        }
+    }
+}
+
+/// 压缩包未通过安全校验，列出所有违规条目
+#[derive(Debug)]
+pub struct UnsafeArchiveError {
+    pub violations: Vec<(String, Violation)>,
+}
+
+impl fmt::Display for UnsafeArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "压缩包未通过安全校验，共 {} 项问题:",
+            self.violations.len()
+        )?;
+        for (name, violation) in &self.violations {
+            write!(f, "\n  - {}: {}", name, violation)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for UnsafeArchiveError {}
+
+/// 条目类型
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    /// 硬链接、设备文件等无法安全解压的类型
+    Special,
+}
+
+/// 由各格式读取器提供的条目元数据，统一交给 [`validate_entries`] 校验
+#[derive(Clone, Debug)]
+pub struct EntryInfo {
+    pub name: String,
+    pub kind: EntryKind,
+    /// 解压后大小
+    pub size: u64,
+    /// 条目压缩后的大小；整体压缩的格式（tar、7z 固实块）无法按条目统计，为 `None`
+    pub compressed_size: Option<u64>,
+    pub metadata: EntryMetadata,
+}
+
+/// 校验 ZIP 压缩包中的所有条目，返回可安全解压的条目列表（已剔除垃圾文件）
+pub fn validate_zip<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    limits: &SafetyLimits,
+) -> Result<Vec<SafeEntry>, Box<dyn Error>> {
+    let mut infos = Vec::with_capacity(archive.len());
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index)?;
+        let is_symlink = entry
+            .unix_mode()
+            .is_some_and(|mode| mode & S_IFMT == S_IFLNK);
+        let kind = if is_symlink {
+            EntryKind::Symlink
+        } else if entry.is_dir() {
+            EntryKind::Dir
+        } else {
+            EntryKind::File
+        };
+        infos.push(EntryInfo {
+            name: entry.name().to_string(),
+            kind,
+            size: entry.size(),
+            compressed_size: Some(entry.compressed_size()),
+            metadata: EntryMetadata::from_zip(&entry),
+        });
+    }
+
+    validate_entries(infos, None, limits)
+}
+
+/// 校验压缩包中的所有条目，返回可安全解压的条目列表（已剔除垃圾文件）。
+///
+/// `SafeEntry::index` 为条目在 `entries` 中的位置；`packed_size` 为整个压缩包的大小，
+/// 用于无法按条目统计压缩比的格式。
+pub fn validate_entries(
+    entries: impl IntoIterator<Item = EntryInfo>,
+    packed_size: Option<u64>,
+    limits: &SafetyLimits,
+) -> Result<Vec<SafeEntry>, Box<dyn Error>> {
+    let mut violations = Vec::new();
+    let mut safe_entries = Vec::new();
+    let mut total_size: u64 = 0;
+    let mut count = 0;
+
+    for (index, entry) in entries.into_iter().enumerate() {
+        count += 1;
+        let name = entry.name;
+
+        match entry.kind {
+            EntryKind::Symlink => {
+                violations.push((name, Violation::Symlink));
+                continue;
+            }
+            EntryKind::Special => {
+                violations.push((name, Violation::SpecialEntry));
+                continue;
+            }
+            EntryKind::File | EntryKind::Dir => {}
+        }
+
+        let path = match sanitize_entry_name(&name) {
+            Ok(Some(path)) => path,
+            Ok(None) => continue,
+            Err(violation) => {
+                violations.push((name, violation));
+                continue;
+            }
+        };
+
+        let size = entry.size;
+        if let Some(compressed) = entry.compressed_size
+            && size > RATIO_CHECK_THRESHOLD
+            && compressed > 0
+        {
+            let ratio = size / compressed;
+            if ratio > limits.max_ratio {
+                violations.push((name.clone(), Violation::RatioTooHigh { ratio }));
+            }
+        }
+
+        total_size = total_size.saturating_add(size);
+        safe_entries.push(SafeEntry {
+            index,
+            path,
+            is_dir: entry.kind == EntryKind::Dir,
+            size,
+            metadata: entry.metadata,
+        });
+    }
+
+    if count > limits.max_entries {
+        violations.insert(
+            0,
+            ("<archive>".to_string(), Violation::TooManyEntries { count }),
+        );
+    }
+
+    if let Some(packed) = packed_size
+        && total_size > RATIO_CHECK_THRESHOLD
+        && packed > 0
+    {
+        let ratio = total_size / packed;
+        if ratio > limits.max_ratio {
+            violations.push(("<archive>".to_string(), Violation::RatioTooHigh { ratio }));
+        }
+    }
+
+    if total_size > limits.max_total_size {
+        violations.push((
+            "<archive>".to_string(),
+            Violation::TotalSizeTooLarge { size: total_size },
+        ));
+    }
+
+    if !violations.is_empty() {
+        return Err(Box::new(UnsafeArchiveError { violations }));
+    }
+
+    Ok(safe_entries)
+}
+
+/// 将压缩包内的条目名称转换为安全的相对路径。
+/// 返回 `Ok(None)` 表示该条目属于应跳过的垃圾文件或空路径。
+pub fn sanitize_entry_name(name: &str) -> Result<Option<PathBuf>, Violation> {
+    if name.contains('\\') {
+        return Err(Violation::Backslash);
+    }
+    if name.starts_with('/') {
+        return Err(Violation::AbsolutePath);
+    }
+    let bytes = name.as_bytes();
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        return Err(Violation::DrivePrefix);
+    }
+
+    let mut path = PathBuf::new();
+    for segment in name.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => return Err(Violation::ParentSegment),
+            s if s.contains(':') => return Err(Violation::DrivePrefix),
+            s if JUNK_SEGMENTS.contains(&s) => return Ok(None),
+            s => path.push(s),
+        }
+    }
+
+    if path.as_os_str().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(path))
+}
+
+/// 复制条目内容，实际解压出的字节数超过声明大小时报错（防止伪造大小的压缩炸弹）
+pub fn copy_entry<R: Read + ?Sized, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    declared_size: u64,
+) -> io::Result<u64> {
+    let copied = io::copy(&mut reader.take(declared_size.saturating_add(1)), writer)?;
+    if copied > declared_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("条目实际大小超过声明的 {} 字节", declared_size),
+        ));
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_normal_segments() {
+        assert_eq!(
+            sanitize_entry_name("STranslate/Plugins/a.dll").unwrap(),
+            Some(PathBuf::from("STranslate/Plugins/a.dll"))
+        );
+        assert_eq!(
+            sanitize_entry_name("./a//b/./c.txt").unwrap(),
+            Some(PathBuf::from("a/b/c.txt"))
+        );
+    }
+
+    #[test]
+    fn rejects_escaping_paths() {
+        assert!(matches!(
+            sanitize_entry_name("a/../../evil.exe"),
+            Err(Violation::ParentSegment)
+        ));
+        assert!(matches!(
+            sanitize_entry_name("/etc/passwd"),
+            Err(Violation::AbsolutePath)
+        ));
+        assert!(matches!(
+            sanitize_entry_name("C:/Windows/evil.dll"),
+            Err(Violation::DrivePrefix)
+        ));
+        assert!(matches!(
+            sanitize_entry_name("a/b:stream"),
+            Err(Violation::DrivePrefix)
+        ));
+        assert!(matches!(
+            sanitize_entry_name("a\\..\\evil.exe"),
+            Err(Violation::Backslash)
+        ));
+    }
+
+    #[test]
+    fn skips_junk_and_empty_entries() {
+        assert_eq!(sanitize_entry_name("__MACOSX/._a.dll").unwrap(), None);
+        assert_eq!(sanitize_entry_name("Plugins/.DS_Store").unwrap(), None);
+        assert_eq!(sanitize_entry_name("./").unwrap(), None);
+        assert_eq!(sanitize_entry_name("").unwrap(), None);
+    }
+}
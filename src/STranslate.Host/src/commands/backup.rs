@@ -11,6 +11,10 @@ use zip::read::ZipArchive;
 use zip::write::FileOptions;
 use zip::{CompressionMethod, ZipWriter};
 
+use crate::archive::metadata::{extended_timestamp_field, zip_options};
+use crate::archive::safety::{SafeEntry, SafetyLimits, copy_entry, validate_zip};
+use crate::disk::{ensure_space, path_size};
+
 #[derive(Clone, Debug, ValueEnum)]
 pub enum BackupMode {
     /// 备份
@@ -73,30 +77,32 @@ pub fn handle_backup_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>>
                 .into());
             }
 
+            check_restore_space(archive, &source_dirs, &targets, verbose)?;
+
             for (source, target) in source_dirs.iter().zip(targets.iter()) {
                 restore_directory(archive, source, target, verbose)?;
                 println!("✅ 恢复完成: {} → {}", source, target);
             }
 
-            if let Some(file_path) = delete_file {
-                if !file_path.trim().is_empty() {
-                    delete_file_or_directory(file_path, verbose)?;
-                }
+            if let Some(file_path) = delete_file
+                && !file_path.trim().is_empty()
+            {
+                delete_file_or_directory(file_path, verbose)?;
             }
         }
     }
 
-    if let Some(file_path) = create_file {
-        if !file_path.trim().is_empty() {
-            let content = file_content.map(|s| s.as_str()).unwrap_or("");
-            create_file_with_content(file_path, content, verbose)?;
-        }
+    if let Some(file_path) = create_file
+        && !file_path.trim().is_empty()
+    {
+        let content = file_content.map(|s| s.as_str()).unwrap_or("");
+        create_file_with_content(file_path, content, verbose)?;
     }
 
-    if let Some(path) = launch_path {
-        if !path.trim().is_empty() {
-            launch_program(path, verbose)?;
-        }
+    if let Some(path) = launch_path
+        && !path.trim().is_empty()
+    {
+        launch_program(path, verbose)?;
     }
 
     Ok(())
@@ -109,10 +115,10 @@ fn backup_directories(
 ) -> Result<(), Box<dyn Error>> {
     let archive_path = Path::new(archive_path);
 
-    if let Some(parent) = archive_path.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent)?;
-        }
+    if let Some(parent) = archive_path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)?;
     }
 
     let archive_file = File::create(archive_path)?;
@@ -168,7 +174,8 @@ fn add_directory_recursively(
 ) -> Result<(), Box<dyn Error>> {
     let relative_str = path_to_zip_string(relative);
     if !relative_str.is_empty() {
-        zip.add_directory(format!("{}/", relative_str), FileOptions::default())?;
+        let options = zip_options(&fs::metadata(source)?, FileOptions::default());
+        zip.add_directory(format!("{}/", relative_str), options)?;
     }
 
     for entry in fs::read_dir(source)? {
@@ -193,7 +200,17 @@ fn add_directory_recursively(
                 println!("   ➕ 文件: {}", path.display());
             }
             let mut input = File::open(&path)?;
-            zip.start_file(path_to_zip_string(&next_relative), file_options())?;
+            let metadata = input.metadata()?;
+            let name = path_to_zip_string(&next_relative);
+            let options = zip_options(&metadata, file_options());
+            match extended_timestamp_field(&metadata) {
+                Some(field) => {
+                    zip.start_file_with_extra_data(name, options)?;
+                    zip.write_all(&field)?;
+                    zip.end_extra_data()?;
+                }
+                None => zip.start_file(name, options)?,
+            }
             io::copy(&mut input, zip)?;
         }
     }
@@ -201,6 +218,36 @@ fn add_directory_recursively(
     Ok(())
 }
 
+/// 恢复前逐个检查目标目录所在磁盘的剩余空间，目标目录的原有内容会在写入前删除
+fn check_restore_space(
+    archive_path: &str,
+    source_dirs: &[&String],
+    targets: &[&String],
+    verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    let archive_path = Path::new(archive_path);
+    if !archive_path.exists() {
+        return Err(format!("备份文件不存在: {}", archive_path.display()).into());
+    }
+
+    let mut archive = ZipArchive::new(File::open(archive_path)?)?;
+    let entries = validate_zip(&mut archive, &SafetyLimits::default())?;
+
+    for (source, target) in source_dirs.iter().zip(targets.iter()) {
+        let source_in_zip = normalize_zip_path(source);
+        let prefix = Path::new(&source_in_zip);
+        let incoming: u64 = entries
+            .iter()
+            .filter(|entry| !entry.is_dir && entry.path.starts_with(prefix))
+            .map(|entry| entry.size)
+            .sum();
+
+        let target_path = Path::new(target.as_str());
+        ensure_space(target_path, incoming, path_size(target_path)?, verbose)?;
+    }
+    Ok(())
+}
+
 fn restore_directory(
     archive_path: &str,
     source_dir: &str,
@@ -224,43 +271,38 @@ fn restore_directory(
         println!("♻️  正在恢复目录 '{}' 到 '{}'", source_in_zip, target_dir);
     }
 
-    let target_path = Path::new(target_dir);
-
-    if target_path.exists() {
-        if target_path.is_dir() {
-            fs::remove_dir_all(target_path)?;
-        } else {
-            fs::remove_file(target_path)?;
-        }
-    }
-    fs::create_dir_all(target_path)?;
-
     let file = File::open(archive_path)?;
     let mut archive = ZipArchive::new(file)?;
     let prefix = Path::new(&source_in_zip);
-    let mut restored_any = false;
 
-    for i in 0..archive.len() {
-        let mut entry = archive.by_index(i)?;
-        let enclosed = match entry.enclosed_name() {
-            Some(path) => path.to_owned(),
-            None => continue,
-        };
+    let entries: Vec<SafeEntry> = validate_zip(&mut archive, &SafetyLimits::default())?
+        .into_iter()
+        .filter(|entry| entry.path.starts_with(prefix))
+        .collect();
+    let restored_any = !entries.is_empty();
 
-        if !enclosed.starts_with(prefix) {
-            continue;
-        }
+    let target_path = Path::new(target_dir);
 
-        restored_any = true;
+    if restored_any {
+        if target_path.exists() {
+            if target_path.is_dir() {
+                fs::remove_dir_all(target_path)?;
+            } else {
+                fs::remove_file(target_path)?;
+            }
+        }
+        fs::create_dir_all(target_path)?;
+    }
 
-        let relative = enclosed.strip_prefix(prefix)?;
+    for entry in &entries {
+        let relative = entry.path.strip_prefix(prefix)?;
         if relative.components().next().is_none() {
             continue;
         }
 
         let out_path = target_path.join(relative);
 
-        if entry.is_dir() {
+        if entry.is_dir {
             fs::create_dir_all(&out_path)?;
             if verbose {
                 println!("📁 创建目录: {}", out_path.display());
@@ -269,8 +311,10 @@ fn restore_directory(
             if let Some(parent) = out_path.parent() {
                 fs::create_dir_all(parent)?;
             }
+            let mut zip_entry = archive.by_index(entry.index)?;
             let mut outfile = File::create(&out_path)?;
-            io::copy(&mut entry, &mut outfile)?;
+            copy_entry(&mut zip_entry, &mut outfile, entry.size)?;
+            entry.metadata.apply_to_file(&outfile, &out_path)?;
 
             if verbose {
                 println!("📝 恢复文件: {}", out_path.display());
@@ -278,6 +322,12 @@ fn restore_directory(
         }
     }
 
+    // 目录的修改时间在其内容全部写入后再还原
+    for entry in entries.iter().filter(|entry| entry.is_dir) {
+        let out_path = target_path.join(entry.path.strip_prefix(prefix)?);
+        entry.metadata.apply_to_dir(&out_path)?;
+    }
+
     if !restored_any {
         return Err(format!("在备份文件中找不到目录: {}", source_in_zip).into());
     }
@@ -316,7 +366,7 @@ fn path_to_zip_string(path: &Path) -> String {
 
 fn normalize_zip_path(value: &str) -> String {
     value
-        .split(|c| c == '/' || c == '\\')
+        .split(['/', '\\'])
         .filter(|segment| !segment.is_empty())
         .collect::<Vec<_>>()
         .join("/")
@@ -365,12 +415,13 @@ fn create_file_with_content(
     let path = Path::new(file_path);
 
     // 如果父目录不存在，则创建
-    if let Some(parent) = path.parent() {
-        if !parent.as_os_str().is_empty() && !parent.exists() {
-            fs::create_dir_all(parent)?;
-            if verbose {
-                println!("📁 创建目录: {}", parent.display());
-            }
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+        && !parent.exists()
+    {
+        fs::create_dir_all(parent)?;
+        if verbose {
+            println!("📁 创建目录: {}", parent.display());
         }
     }
 
@@ -6,4 +6,8 @@ pub mod update;
 pub use backup::{BackupMode, handle_backup_command};
 pub use start::{StartMode, handle_start_command};
 pub use task::{TaskAction, handle_task_command};
-pub use update::handle_update_command;
+pub use update::{
+    Channel, LaunchPrivilege, RecoverMode, SKIP_UAC_TASK_NAME, finish_host_update_on_launch,
+    handle_apply_pending_command, handle_check_update_command, handle_recover_command,
+    handle_update_command, update_exit_code,
+};
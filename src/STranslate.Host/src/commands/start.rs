@@ -1,9 +1,12 @@
 use clap::{ArgMatches, ValueEnum};
 use std::error::Error;
+#[cfg(target_os = "windows")]
 use std::process::{Command as ProcessCommand, Stdio};
 use std::thread;
 use std::time::Duration;
 
+use super::update::apply_pending_on_launch;
+
 #[derive(Clone, Debug, ValueEnum)]
 pub enum StartMode {
     /// 直接启动进程
@@ -43,6 +46,8 @@ pub fn handle_start_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>>
         thread::sleep(Duration::from_secs(delay));
     }
 
+    apply_pending_on_launch(verbose);
+
     match mode {
         StartMode::Direct => {
             start_direct_process(target, &args, verbose)?;
@@ -59,6 +64,7 @@ pub fn handle_start_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>>
     Ok(())
 }
 
+#[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
 fn start_direct_process(
     target: &str,
     args: &[&String],
@@ -102,6 +108,7 @@ fn start_direct_process(
     Ok(())
 }
 
+#[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
 fn start_elevated_process(
     target: &str,
     args: &[&String],
@@ -143,7 +150,7 @@ fn start_elevated_process(
     Ok(())
 }
 
-fn start_task_scheduler(task_name: &str, verbose: bool) -> Result<(), Box<dyn Error>> {
+pub fn start_task_scheduler(task_name: &str, verbose: bool) -> Result<(), Box<dyn Error>> {
     if verbose {
         println!("📅 启动任务计划: {}", task_name);
     }
@@ -1,8 +1,12 @@
+#[cfg(target_os = "windows")]
 use chrono::Local;
 use clap::{ArgMatches, ValueEnum};
 use std::error::Error;
+#[cfg(target_os = "windows")]
 use std::fs;
+#[cfg(target_os = "windows")]
 use std::path::Path;
+#[cfg(target_os = "windows")]
 use std::process::Command as ProcessCommand;
 
 #[derive(Clone, Debug, ValueEnum)]
@@ -17,6 +21,7 @@ pub enum TaskAction {
     List,
 }
 
+#[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
 pub fn handle_task_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
     let action = matches.get_one::<TaskAction>("action").unwrap();
     let verbose = matches.get_flag("verbose");
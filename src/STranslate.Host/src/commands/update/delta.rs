@@ -0,0 +1,403 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use super::manifest::{PackageManifest, sha256_file, to_hex};
+use super::version::Version;
+use crate::archive::safety::sanitize_entry_name;
+use crate::disk::ensure_space;
+
+/// 更新包中存放补丁的目录
+pub const DELTA_DIR_NAME: &str = ".delta";
+
+/// 清单中的差分信息。
+///
+/// 差分包只包含新增文件与 `.delta/<path>` 下的补丁，`files` 仍描述更新后的完整文件列表；
+/// 包内既没有文件也没有补丁的条目视为未改动，从程序目录复制，最终结果仍按 `files` 校验。
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeltaManifest {
+    /// 差分包所基于的版本
+    #[serde(default)]
+    pub base_version: Option<String>,
+    #[serde(default)]
+    pub patches: Vec<DeltaPatch>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeltaPatch {
+    /// 以 `/` 分隔的相对路径，补丁位于 `.delta/<path>`
+    pub path: String,
+    pub method: PatchMethod,
+    /// 基础版本中该文件的 SHA-256
+    pub base_sha256: String,
+}
+
+/// 补丁格式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PatchMethod {
+    /// zstd 压缩的 bsdiff 补丁
+    Bsdiff,
+    /// 以基础文件为字典的 zstd 帧（`zstd --patch-from`）
+    Zstd,
+}
+
+/// 程序目录中的基础文件与差分包不匹配，需要改用完整更新包
+#[derive(Debug)]
+pub struct DeltaBaseMismatch(pub String);
+
+impl fmt::Display for DeltaBaseMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "差分包的基础版本与当前安装不匹配: {}", self.0)
+    }
+}
+
+impl Error for DeltaBaseMismatch {}
+
+/// 在暂存目录中还原完整文件：应用补丁并复制未改动的文件，完成后删除补丁目录
+pub fn apply_delta(
+    manifest: &PackageManifest,
+    delta: &DeltaManifest,
+    install_dir: &Path,
+    installed_version: Option<&Version>,
+    staging_dir: &Path,
+    verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    if let (Some(base), Some(installed)) = (delta.base_version.as_deref(), installed_version) {
+        let base = Version::parse(base).ok_or_else(|| format!("差分包基础版本号无效: {}", base))?;
+        if base != *installed {
+            return Err(Box::new(DeltaBaseMismatch(format!(
+                "需要 {}，当前为 {}",
+                base, installed
+            ))));
+        }
+    }
+
+    // 清单中的路径在拼接到程序目录与暂存目录之前先校验，拒绝绝对路径与 `..`
+    let mut files = Vec::with_capacity(manifest.files.len());
+    for file in &manifest.files {
+        files.push((manifest_path(&file.path)?, file));
+    }
+
+    // 还原出的文件在解压前无法得知大小，此时根据清单再检查一次
+    let incoming = files
+        .iter()
+        .filter(|(relative, _)| !staging_dir.join(relative).exists())
+        .map(|(_, file)| file.size)
+        .sum();
+    ensure_space(install_dir, incoming, 0, verbose)?;
+
+    let patch_dir = staging_dir.join(DELTA_DIR_NAME);
+    for patch in &delta.patches {
+        let (relative, expected_size) = files
+            .iter()
+            .find(|(_, file)| file.path == patch.path)
+            .map(|(relative, file)| (relative, file.size))
+            .ok_or_else(|| format!("补丁对应的文件不在清单中: {}", patch.path))?;
+
+        let base_path = install_dir.join(relative);
+        let base = match fs::read(&base_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(Box::new(DeltaBaseMismatch(format!("缺少 {}", patch.path))));
+            }
+            Err(e) => {
+                return Err(format!("读取基础文件失败: {} ({})", base_path.display(), e).into());
+            }
+        };
+        if !to_hex(&Sha256::digest(&base)).eq_ignore_ascii_case(&patch.base_sha256) {
+            return Err(Box::new(DeltaBaseMismatch(format!(
+                "{} 已被修改",
+                patch.path
+            ))));
+        }
+
+        let patch_path = patch_dir.join(relative);
+        let output = apply_patch(patch.method, &base, &patch_path, expected_size)
+            .map_err(|e| format!("应用补丁失败: {} ({})", patch.path, e))?;
+
+        let target = staging_dir.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&target, output)?;
+        if verbose {
+            println!("🩹 已应用补丁: {}", patch.path);
+        }
+    }
+
+    if patch_dir.exists() {
+        fs::remove_dir_all(&patch_dir)?;
+    }
+
+    let mut unchanged = 0;
+    for (relative, file) in &files {
+        let target = staging_dir.join(relative);
+        if target.exists() {
+            continue;
+        }
+
+        let source = install_dir.join(relative);
+        let matches = source.is_file()
+            && fs::metadata(&source)?.len() == file.size
+            && sha256_file(&source)?.eq_ignore_ascii_case(&file.sha256);
+        if !matches {
+            return Err(Box::new(DeltaBaseMismatch(format!(
+                "未改动的文件 {} 缺失或已被修改",
+                file.path
+            ))));
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&source, &target)?;
+        unchanged += 1;
+    }
+
+    println!(
+        "✅ 差分包已还原: {} 个补丁，{} 个未改动文件",
+        delta.patches.len(),
+        unchanged
+    );
+    Ok(())
+}
+
+/// 将清单中的路径转换为安全的相对路径
+fn manifest_path(path: &str) -> Result<PathBuf, Box<dyn Error>> {
+    match sanitize_entry_name(path) {
+        Ok(Some(relative)) => Ok(relative),
+        Ok(None) => Err(format!("差分包清单中的路径无效: {}", path).into()),
+        Err(violation) => Err(format!("差分包清单中的路径不安全: {} ({})", path, violation).into()),
+    }
+}
+
+/// 应用单个补丁，输出一旦超过清单中声明的大小即停止并视为损坏
+fn apply_patch(
+    method: PatchMethod,
+    base: &[u8],
+    patch_path: &Path,
+    expected_size: u64,
+) -> io::Result<Vec<u8>> {
+    let file = BufReader::new(File::open(patch_path)?);
+
+    let output = match method {
+        PatchMethod::Bsdiff => {
+            let mut decoder = zstd::stream::read::Decoder::new(file)?;
+            bspatch(base, &mut decoder, expected_size)?
+        }
+        PatchMethod::Zstd => {
+            let mut decoder = zstd::stream::read::Decoder::with_dictionary(file, base)?;
+            decoder.window_log_max(31)?;
+            let mut output = Vec::new();
+            decoder.take(expected_size + 1).read_to_end(&mut output)?;
+            output
+        }
+    };
+
+    if output.len() as u64 != expected_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("输出大小 {} 与清单不符 ({})", output.len(), expected_size),
+        ));
+    }
+    Ok(output)
+}
+
+/// 按 bsdiff 格式应用补丁：每个控制块为混合长度、追加长度与旧文件偏移（各 8 字节），
+/// 随后是混合数据与追加数据。每段读取前先检查输出是否会超过 `limit`，伪造的长度不会触发大量分配。
+fn bspatch(old: &[u8], patch: &mut impl Read, limit: u64) -> io::Result<Vec<u8>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut output = Vec::new();
+    let mut old_pos: u64 = 0;
+    let mut control = [0u8; 24];
+
+    while read_control(patch, &mut control)? {
+        let mix_len = u64::from_le_bytes(control[0..8].try_into().unwrap());
+        let copy_len = u64::from_le_bytes(control[8..16].try_into().unwrap());
+        let seek = sign_magnitude(control[16..24].try_into().unwrap());
+
+        let len = mix_len
+            .checked_add(copy_len)
+            .ok_or_else(|| invalid("控制块长度溢出"))?;
+        if output.len() as u64 + len > limit {
+            return Err(invalid(&format!("输出超过清单中的大小 ({})", limit)));
+        }
+
+        let start = output.len();
+        let read = patch.take(len).read_to_end(&mut output)?;
+        if read as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let old_end = old_pos
+            .checked_add(mix_len)
+            .ok_or_else(|| invalid("旧文件偏移溢出"))?;
+        let old_slice = usize::try_from(old_pos)
+            .ok()
+            .zip(usize::try_from(old_end).ok())
+            .and_then(|(from, to)| old.get(from..to))
+            .ok_or_else(|| invalid("补丁引用超出基础文件"))?;
+        let mix_end = start + mix_len as usize;
+        for (byte, base) in output[start..mix_end].iter_mut().zip(old_slice) {
+            *byte = byte.wrapping_add(*base);
+        }
+
+        old_pos = i64::try_from(old_end)
+            .ok()
+            .and_then(|pos| pos.checked_add(seek))
+            .and_then(|pos| u64::try_from(pos).ok())
+            .ok_or_else(|| invalid("旧文件偏移无效"))?;
+    }
+    Ok(output)
+}
+
+/// 读取 24 字节的控制块，补丁正常结束时返回 `false`
+fn read_control(patch: &mut impl Read, control: &mut [u8; 24]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < control.len() {
+        match patch.read(&mut control[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// bsdiff 以符号位加绝对值的形式存储偏移
+fn sign_magnitude(bytes: [u8; 8]) -> i64 {
+    let value = u64::from_le_bytes(bytes);
+    let magnitude = (value & !(1 << 63)) as i64;
+    if value & (1 << 63) == 0 {
+        magnitude
+    } else {
+        -magnitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::update::manifest::ManifestFile;
+
+    fn manifest_file(path: &str, content: &[u8]) -> ManifestFile {
+        ManifestFile {
+            path: path.to_string(),
+            size: content.len() as u64,
+            sha256: to_hex(&Sha256::digest(content)),
+        }
+    }
+
+    fn bsdiff_patch(old: &[u8], new: &[u8]) -> Vec<u8> {
+        let mut patch = Vec::new();
+        bsdiff::diff(old, new, &mut patch).unwrap();
+        patch
+    }
+
+    #[test]
+    fn bspatch_restores_the_new_file() {
+        let old = b"STranslate 1.0 main program".repeat(20);
+        let new = b"STranslate 2.0 main program, now faster".repeat(20);
+        let patch = bsdiff_patch(&old, &new);
+        let output = bspatch(&old, &mut patch.as_slice(), new.len() as u64).unwrap();
+        assert_eq!(output, new);
+    }
+
+    #[test]
+    fn bspatch_stops_once_output_exceeds_limit() {
+        let old = b"base".repeat(100);
+        let new = b"grown".repeat(1000);
+        let patch = bsdiff_patch(&old, &new);
+        let error = bspatch(&old, &mut patch.as_slice(), 100).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn bspatch_rejects_forged_lengths_without_allocating() {
+        let mut patch = Vec::new();
+        patch.extend_from_slice(&(u64::MAX / 2).to_le_bytes());
+        patch.extend_from_slice(&0u64.to_le_bytes());
+        patch.extend_from_slice(&0u64.to_le_bytes());
+        assert!(bspatch(b"", &mut patch.as_slice(), 1024).is_err());
+    }
+
+    #[test]
+    fn oversized_patch_output_is_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        let install = root.path().join("install");
+        let staging = root.path().join("staging");
+        fs::create_dir_all(&install).unwrap();
+        fs::create_dir_all(staging.join(DELTA_DIR_NAME)).unwrap();
+
+        let old = b"old content".repeat(10);
+        let new = b"much larger new content".repeat(200);
+        fs::write(install.join("app.dll"), &old).unwrap();
+        let patch = zstd::stream::encode_all(bsdiff_patch(&old, &new).as_slice(), 0).unwrap();
+        fs::write(staging.join(DELTA_DIR_NAME).join("app.dll"), patch).unwrap();
+
+        // 清单声明的大小小于补丁实际输出
+        let mut file = manifest_file("app.dll", &new);
+        file.size = 64;
+        let manifest = PackageManifest {
+            files: vec![file],
+            ..Default::default()
+        };
+        let delta = DeltaManifest {
+            base_version: None,
+            patches: vec![DeltaPatch {
+                path: "app.dll".to_string(),
+                method: PatchMethod::Bsdiff,
+                base_sha256: to_hex(&Sha256::digest(&old)),
+            }],
+        };
+
+        let error = apply_delta(&manifest, &delta, &install, None, &staging, false).unwrap_err();
+        assert!(error.to_string().contains("应用补丁失败"), "{}", error);
+        assert!(!staging.join("app.dll").exists());
+    }
+
+    #[test]
+    fn traversal_paths_are_rejected_before_any_access() {
+        let root = tempfile::tempdir().unwrap();
+        let install = root.path().join("install");
+        let staging = root.path().join("staging");
+        fs::create_dir_all(&install).unwrap();
+        fs::create_dir_all(staging.join(DELTA_DIR_NAME)).unwrap();
+        fs::write(root.path().join("secret.txt"), b"secret").unwrap();
+
+        for path in [
+            "../secret.txt",
+            "/etc/passwd",
+            "C:/Windows/win.ini",
+            "a/../../b",
+        ] {
+            let manifest = PackageManifest {
+                files: vec![manifest_file(path, b"secret")],
+                ..Default::default()
+            };
+            let delta = DeltaManifest {
+                base_version: None,
+                patches: vec![DeltaPatch {
+                    path: path.to_string(),
+                    method: PatchMethod::Zstd,
+                    base_sha256: to_hex(&Sha256::digest(b"secret")),
+                }],
+            };
+
+            let error =
+                apply_delta(&manifest, &delta, &install, None, &staging, false).unwrap_err();
+            assert!(error.to_string().contains("不安全"), "{}: {}", path, error);
+        }
+        assert!(!root.path().join("b").exists());
+        assert_eq!(fs::read_dir(&staging).unwrap().count(), 1);
+    }
+}
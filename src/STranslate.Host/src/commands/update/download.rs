@@ -0,0 +1,587 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use super::manifest::{sha256_file, to_hex};
+use super::signature::SIGNATURE_SUFFIX;
+use crate::proxy::ProxyConfig;
+
+/// 下载缓存目录名称（位于程序目录下），保存下载中与已下载的更新包
+pub const CACHE_DIR_NAME: &str = ".update-cache";
+
+/// 未下载完成的文件后缀，完成并校验通过后去掉
+const PARTIAL_SUFFIX: &str = ".part";
+/// 记录断点续传所需的服务端校验信息
+const PARTIAL_META_SUFFIX: &str = ".part.json";
+/// 与更新包并列发布的校验值文件后缀，例如 `STranslate.zip.sha256`
+const CHECKSUM_SUFFIX: &str = ".sha256";
+/// 第一次重试前的等待时间，之后每次翻倍
+const RETRY_INITIAL_DELAY: Duration = Duration::from_secs(1);
+
+/// 判断参数是否为 HTTP(S) 下载地址
+pub fn is_url(value: &str) -> bool {
+    let lower = value.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// 下载的可选行为
+#[derive(Clone)]
+pub struct DownloadOptions {
+    pub cache_dir: PathBuf,
+    /// 期望的 SHA-256，未指定时尝试读取 `<url>.sha256`
+    pub sha256: Option<String>,
+    /// 连接与读取超时
+    pub timeout: Duration,
+    /// 失败后的重试次数（不含第一次）
+    pub retries: u32,
+    pub proxy: ProxyConfig,
+    pub verbose: bool,
+}
+
+/// 下载到缓存目录的更新包
+pub struct DownloadedPackage {
+    pub path: PathBuf,
+    /// 下载内容已按命令行 `--sha256` 校验；`<url>.sha256` 与更新包来自同一服务器，只用于发现传输损坏
+    pub checksum_verified: bool,
+}
+
+/// 下载内容与期望的校验值不一致
+#[derive(Debug)]
+pub struct ChecksumMismatch {
+    pub url: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "下载内容校验失败: {}\n  期望 SHA-256: {}\n  实际 SHA-256: {}",
+            self.url, self.expected, self.actual
+        )
+    }
+}
+
+impl Error for ChecksumMismatch {}
+
+/// 断点续传时用于确认服务端文件未变化的信息
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PartialMeta {
+    url: String,
+    #[serde(default)]
+    etag: Option<String>,
+    #[serde(default)]
+    last_modified: Option<String>,
+}
+
+/// 下载更新包到缓存目录并校验，返回本地路径以及是否已按校验值校验。
+///
+/// 中断后再次执行时通过 `Range` 请求续传；同时尝试下载 `<url>.minisig` 供签名校验使用。
+/// 未通过命令行 `--sha256` 校验时不会在此拒绝，由更新事务在签名校验后决定是否允许安装。
+pub fn download_package(
+    url: &str,
+    options: &DownloadOptions,
+) -> Result<DownloadedPackage, Box<dyn Error>> {
+    fs::create_dir_all(&options.cache_dir).map_err(|e| {
+        format!(
+            "创建下载缓存目录失败: {} ({})",
+            options.cache_dir.display(),
+            e
+        )
+    })?;
+
+    let agent = options.proxy.agent(url, options.timeout)?;
+    if options.verbose {
+        println!("🌐 网络连接: {}", options.proxy.describe(url)?);
+    }
+    let target = options.cache_dir.join(file_name_from_url(url));
+
+    let (expected, checksum_verified) = match &options.sha256 {
+        Some(sha256) => (Some(sha256.trim().to_ascii_lowercase()), true),
+        None => (fetch_checksum(&agent, url, options), false),
+    };
+
+    // 已下载且校验通过的文件直接复用
+    if let Some(expected) = &expected
+        && target.is_file()
+        && sha256_file(&target)? == *expected
+    {
+        println!("♻️  使用已下载的更新包: {}", target.display());
+        return Ok(DownloadedPackage {
+            path: target,
+            checksum_verified,
+        });
+    }
+
+    println!("🌐 正在下载更新包: {}", url);
+    let partial = with_suffix(&target, PARTIAL_SUFFIX);
+    with_retries(options, || {
+        download_to(&agent, url, &target, options.verbose)
+    })?;
+
+    if let Some(expected) = expected {
+        let actual = sha256_file(&partial)?;
+        if actual != expected {
+            let _ = fs::remove_file(&partial);
+            let _ = fs::remove_file(with_suffix(&target, PARTIAL_META_SUFFIX));
+            return Err(Box::new(ChecksumMismatch {
+                url: url.to_string(),
+                expected,
+                actual,
+            }));
+        }
+        println!("✅ 下载内容校验通过 (SHA-256)");
+    }
+
+    fs::rename(&partial, &target)?;
+    let _ = fs::remove_file(with_suffix(&target, PARTIAL_META_SUFFIX));
+    println!("✅ 下载完成: {}", target.display());
+
+    fetch_signature(&agent, url, &target, options);
+    Ok(DownloadedPackage {
+        path: target,
+        checksum_verified,
+    })
+}
+
+/// 单次下载尝试：已有部分内容时请求剩余部分，服务端不支持续传或文件已变化时从头下载
+fn download_to(
+    agent: &ureq::Agent,
+    url: &str,
+    target: &Path,
+    verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    let partial = &with_suffix(target, PARTIAL_SUFFIX);
+    let meta_path = with_suffix(target, PARTIAL_META_SUFFIX);
+    let meta: PartialMeta = fs::read_to_string(&meta_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .filter(|meta: &PartialMeta| meta.url == url)
+        .unwrap_or_default();
+    let existing = match fs::metadata(partial) {
+        Ok(metadata) if meta.url == url => metadata.len(),
+        _ => 0,
+    };
+
+    let mut request = agent.get(url);
+    if existing > 0 {
+        request = request.set("Range", &format!("bytes={}-", existing));
+        if let Some(validator) = meta.etag.as_deref().or(meta.last_modified.as_deref()) {
+            request = request.set("If-Range", validator);
+        }
+    }
+
+    let response = match request.call() {
+        Ok(response) => response,
+        // 请求的范围超出文件大小，说明缓存的部分内容已失效
+        Err(ureq::Error::Status(416, _)) => {
+            fs::remove_file(partial)?;
+            return Err("服务端拒绝续传请求，将重新下载".into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let resumed = existing > 0 && response.status() == 206;
+    let total = response
+        .header("Content-Length")
+        .and_then(|length| length.parse::<u64>().ok())
+        .map(|length| if resumed { length + existing } else { length });
+
+    PartialMeta {
+        url: url.to_string(),
+        etag: response.header("ETag").map(str::to_string),
+        last_modified: response.header("Last-Modified").map(str::to_string),
+    }
+    .write(&meta_path)?;
+
+    let mut file = if resumed {
+        if verbose {
+            println!("⏯️  从 {} 字节处继续下载", existing);
+        }
+        OpenOptions::new().append(true).open(partial)?
+    } else {
+        File::create(partial)?
+    };
+
+    let mut reader = response.into_reader();
+    let mut progress = DownloadProgress::new(if resumed { existing } else { 0 }, total, verbose);
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read])?;
+        progress.advance(read as u64);
+    }
+    file.sync_all()?;
+
+    if let Some(total) = total
+        && progress.downloaded < total
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!(
+                "连接提前关闭: 已下载 {} / {} 字节",
+                progress.downloaded, total
+            ),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// 按指数退避重试下载；HTTP 4xx（416 除外）不会因重试而改变，直接失败
+fn with_retries(
+    options: &DownloadOptions,
+    mut attempt: impl FnMut() -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut delay = RETRY_INITIAL_DELAY;
+    let mut retried = 0;
+    loop {
+        match attempt() {
+            Ok(()) => return Ok(()),
+            Err(e) if retried < options.retries && is_retryable(e.as_ref()) => {
+                retried += 1;
+                println!(
+                    "⚠️  下载失败: {}，{} 秒后重试 ({}/{})",
+                    e,
+                    delay.as_secs(),
+                    retried,
+                    options.retries
+                );
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            Err(e) => return Err(format!("下载失败: {}", e).into()),
+        }
+    }
+}
+
+fn is_retryable(error: &(dyn Error + 'static)) -> bool {
+    match error.downcast_ref::<ureq::Error>() {
+        Some(ureq::Error::Status(code, _)) => *code >= 500 || *code == 408 || *code == 429,
+        Some(ureq::Error::Transport(_)) => true,
+        None => true,
+    }
+}
+
+/// 读取 `<url>.sha256`，内容为十六进制校验值，可带文件名（`sha256sum` 输出格式）。
+///
+/// 不存在、获取失败或格式无效时一律视为没有校验值，输出原因后继续。
+fn fetch_checksum(agent: &ureq::Agent, url: &str, options: &DownloadOptions) -> Option<String> {
+    let checksum_url = format!("{}{}", url, CHECKSUM_SUFFIX);
+    let body = agent
+        .get(&checksum_url)
+        .call()
+        .map_err(|e| match e {
+            ureq::Error::Status(code, _) => format!("HTTP {}", code),
+            e => e.to_string(),
+        })
+        .and_then(|response| response.into_string().map_err(|e| e.to_string()));
+    let checksum = body.and_then(|body| {
+        body.split_whitespace()
+            .next()
+            .filter(|value| value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit()))
+            .map(str::to_ascii_lowercase)
+            .ok_or_else(|| "格式无效".to_string())
+    });
+
+    match checksum {
+        Ok(checksum) => {
+            if options.verbose {
+                println!("🔑 已获取校验值: {}", checksum_url);
+            }
+            Some(checksum)
+        }
+        Err(reason) => {
+            println!(
+                "⚠️  未提供校验值 (--sha256)，且无法使用 {}: {}",
+                checksum_url, reason
+            );
+            None
+        }
+    }
+}
+
+/// 尝试下载与更新包并列发布的签名文件，不存在时忽略，是否必须校验签名由签名选项决定
+fn fetch_signature(agent: &ureq::Agent, url: &str, target: &Path, options: &DownloadOptions) {
+    let signature_url = format!("{}{}", url, SIGNATURE_SUFFIX);
+    let signature_path = with_suffix(target, SIGNATURE_SUFFIX);
+    match agent.get(&signature_url).call() {
+        Ok(response) => {
+            let result = File::create(&signature_path)
+                .and_then(|mut file| io::copy(&mut response.into_reader(), &mut file));
+            if let Err(e) = result {
+                let _ = fs::remove_file(&signature_path);
+                println!("⚠️  保存签名文件失败: {} ({})", signature_path.display(), e);
+            } else if options.verbose {
+                println!("🔏 已下载签名文件: {}", signature_path.display());
+            }
+        }
+        Err(ureq::Error::Status(404, _)) => {}
+        Err(e) => println!("⚠️  下载签名文件失败: {} ({})", signature_url, e),
+    }
+}
+
+impl PartialMeta {
+    fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// 取 URL 路径的最后一段作为文件名，去掉查询参数并替换不能用于文件名的字符。
+///
+/// 文件名前加上 URL 哈希的前 8 位，文件名相同的不同地址（例如差分包与完整包）不会共用缓存文件。
+fn file_name_from_url(url: &str) -> String {
+    let prefix = &to_hex(&Sha256::digest(url.as_bytes()))[..8];
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let name = path
+        .rsplit('/')
+        .next()
+        .unwrap_or("")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "._-+".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    if name.trim_matches('.').is_empty() || name.contains("://") {
+        format!("{}-update-package", prefix)
+    } else {
+        format!("{}-{}", prefix, name)
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// 下载进度，开启详细输出时每增加 10% 输出一次；服务端未返回长度时每 10 MB 输出一次
+struct DownloadProgress {
+    verbose: bool,
+    total: Option<u64>,
+    downloaded: u64,
+    next_report: u64,
+}
+
+impl DownloadProgress {
+    const UNKNOWN_STEP: u64 = 10 * 1024 * 1024;
+
+    fn new(downloaded: u64, total: Option<u64>, verbose: bool) -> Self {
+        let mut progress = Self {
+            verbose,
+            total,
+            downloaded,
+            next_report: 0,
+        };
+        progress.next_report = progress.step_after(downloaded);
+        progress
+    }
+
+    fn step_after(&self, downloaded: u64) -> u64 {
+        match self.total {
+            Some(total) if total > 0 => {
+                let step = total.div_ceil(10);
+                (downloaded / step + 1) * step
+            }
+            _ => (downloaded / Self::UNKNOWN_STEP + 1) * Self::UNKNOWN_STEP,
+        }
+    }
+
+    fn advance(&mut self, bytes: u64) {
+        self.downloaded += bytes;
+        if !self.verbose || self.downloaded < self.next_report {
+            return;
+        }
+
+        match self.total {
+            Some(total) if total > 0 => println!(
+                "   下载进度: {}% ({} / {} 字节)",
+                self.downloaded.saturating_mul(100) / total,
+                self.downloaded,
+                total
+            ),
+            _ => println!("   已下载: {} 字节", self.downloaded),
+        }
+        self.next_report = self.step_after(self.downloaded);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::ProxyOptions;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn cache_name_keeps_file_name_and_separates_urls() {
+        let delta = file_name_from_url("https://example.com/delta/STranslate.zip?token=1");
+        let full = file_name_from_url("https://example.com/full/STranslate.zip");
+        assert!(delta.ends_with("-STranslate.zip"));
+        assert!(full.ends_with("-STranslate.zip"));
+        assert_ne!(delta, full);
+        assert_eq!(
+            full,
+            file_name_from_url("https://example.com/full/STranslate.zip")
+        );
+        assert!(file_name_from_url("https://example.com/").ends_with("-update-package"));
+        assert!(file_name_from_url("https://example.com/a b.zip").ends_with("-a_b.zip"));
+    }
+
+    /// 本地 HTTP 服务：提供 `/pkg.zip`（支持 `Range` 续传）与可选的 `/pkg.zip.sha256`，
+    /// 返回地址前缀与收到的请求记录（路径与续传起点）
+    fn serve(content: Vec<u8>, checksum: Option<String>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line
+                    .split_whitespace()
+                    .nth(1)
+                    .unwrap_or("")
+                    .to_string();
+                let mut range = None;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                        range = value.trim().trim_end_matches('-').parse::<usize>().ok();
+                    }
+                }
+                log.lock().unwrap().push(format!("{} {:?}", path, range));
+
+                let (status, extra, body) = match (path.as_str(), range) {
+                    ("/pkg.zip", Some(start)) => (
+                        "206 Partial Content",
+                        format!(
+                            "Content-Range: bytes {}-{}/{}\r\n",
+                            start,
+                            content.len() - 1,
+                            content.len()
+                        ),
+                        content[start..].to_vec(),
+                    ),
+                    ("/pkg.zip", None) => ("200 OK", String::new(), content.clone()),
+                    ("/pkg.zip.sha256", _) if checksum.is_some() => (
+                        "200 OK",
+                        String::new(),
+                        format!("{}  pkg.zip\n", checksum.as_ref().unwrap()).into_bytes(),
+                    ),
+                    _ => ("404 Not Found", String::new(), Vec::new()),
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nETag: \"v1\"\r\n{}Connection: close\r\n\r\n",
+                    status,
+                    body.len(),
+                    extra
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(&body);
+            }
+        });
+        (base, requests)
+    }
+
+    fn options(cache_dir: &Path, sha256: Option<String>) -> DownloadOptions {
+        DownloadOptions {
+            cache_dir: cache_dir.to_path_buf(),
+            sha256,
+            timeout: Duration::from_secs(5),
+            retries: 0,
+            proxy: ProxyConfig::resolve(
+                &ProxyOptions {
+                    no_proxy: true,
+                    ..Default::default()
+                },
+                None,
+            )
+            .unwrap(),
+            verbose: false,
+        }
+    }
+
+    fn package_content() -> Vec<u8> {
+        (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn resumes_partial_download_with_range() {
+        let content = package_content();
+        let sha256 = to_hex(&Sha256::digest(&content));
+        let (base, requests) = serve(content.clone(), None);
+        let url = format!("{}/pkg.zip", base);
+
+        // 模拟上次中断：缓存中已有前 50000 字节与服务端校验信息
+        let cache = tempfile::tempdir().unwrap();
+        let target = cache.path().join(file_name_from_url(&url));
+        fs::write(with_suffix(&target, PARTIAL_SUFFIX), &content[..50_000]).unwrap();
+        PartialMeta {
+            url: url.clone(),
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+        }
+        .write(&with_suffix(&target, PARTIAL_META_SUFFIX))
+        .unwrap();
+
+        let package = download_package(&url, &options(cache.path(), Some(sha256))).unwrap();
+        assert!(package.checksum_verified);
+        assert_eq!(fs::read(&package.path).unwrap(), content);
+        assert!(!with_suffix(&target, PARTIAL_SUFFIX).exists());
+        assert!(
+            requests
+                .lock()
+                .unwrap()
+                .contains(&"/pkg.zip Some(50000)".to_string())
+        );
+    }
+
+    #[test]
+    fn sidecar_checksum_does_not_count_as_verified() {
+        let content = package_content();
+        let sha256 = to_hex(&Sha256::digest(&content));
+        let (base, _) = serve(content.clone(), Some(sha256));
+        let cache = tempfile::tempdir().unwrap();
+
+        let package =
+            download_package(&format!("{}/pkg.zip", base), &options(cache.path(), None)).unwrap();
+        assert!(!package.checksum_verified);
+        assert_eq!(fs::read(&package.path).unwrap(), content);
+    }
+
+    #[test]
+    fn sidecar_checksum_still_rejects_corrupted_downloads() {
+        let (base, _) = serve(package_content(), Some("0".repeat(64)));
+        let cache = tempfile::tempdir().unwrap();
+
+        let error = download_package(&format!("{}/pkg.zip", base), &options(cache.path(), None))
+            .err()
+            .unwrap();
+        assert!(error.is::<ChecksumMismatch>());
+    }
+}
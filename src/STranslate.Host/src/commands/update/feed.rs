@@ -0,0 +1,191 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use super::download::is_url;
+use super::version::Version;
+use crate::proxy::ProxyConfig;
+
+/// 更新渠道
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// 只接收正式版
+    Stable,
+    /// 同时接收测试版与正式版
+    Beta,
+}
+
+impl Channel {
+    /// 判断发布源中标记的渠道是否可以在当前渠道中安装，未知渠道一律忽略
+    fn accepts(self, channel: &str) -> bool {
+        match self {
+            Channel::Stable => channel.eq_ignore_ascii_case("stable"),
+            Channel::Beta => {
+                channel.eq_ignore_ascii_case("stable") || channel.eq_ignore_ascii_case("beta")
+            }
+        }
+    }
+}
+
+/// 发布源，列出可供安装的版本
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseFeed {
+    #[serde(default)]
+    pub releases: Vec<Release>,
+}
+
+/// 发布源中的一个版本
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Release {
+    pub version: String,
+    #[serde(default = "default_channel")]
+    pub channel: String,
+    /// 完整更新包地址，相对地址按发布源所在位置解析
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// 从指定旧版本升级时可用的差分包
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deltas: Vec<DeltaRelease>,
+}
+
+/// 发布源中的差分包
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeltaRelease {
+    /// 差分包的基础版本
+    pub from: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+fn default_channel() -> String {
+    "stable".to_string()
+}
+
+/// 检查更新的结果，以 JSON 输出供脚本使用
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCheck {
+    pub update_available: bool,
+    pub installed_version: Option<String>,
+    pub channel: Channel,
+    /// 可安装的最新版本，差分包只保留与当前版本匹配的一项
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update: Option<Release>,
+}
+
+/// 从 HTTP(S) 地址或本地文件读取发布源，并将其中的相对地址解析为完整地址
+pub fn load_feed(
+    source: &str,
+    proxy: &ProxyConfig,
+    timeout: Duration,
+) -> Result<ReleaseFeed, Box<dyn Error>> {
+    let content = if is_url(source) {
+        proxy
+            .agent(source, timeout)?
+            .get(source)
+            .call()
+            .map_err(|e| format!("获取发布源失败: {}", e))?
+            .into_string()?
+    } else {
+        fs::read_to_string(source).map_err(|e| format!("读取发布源失败: {} ({})", source, e))?
+    };
+
+    let mut feed: ReleaseFeed = serde_json::from_str(&content)
+        .map_err(|e| format!("解析发布源失败: {} ({})", source, e))?;
+    for release in &mut feed.releases {
+        release.url = resolve_url(source, &release.url);
+        for delta in &mut release.deltas {
+            delta.url = resolve_url(source, &delta.url);
+        }
+    }
+    Ok(feed)
+}
+
+/// 在当前渠道中选出高于已安装版本的最新版本；版本未知时选出最新版本
+pub fn check_update(
+    feed: &ReleaseFeed,
+    installed: Option<&Version>,
+    channel: Channel,
+    verbose: bool,
+) -> UpdateCheck {
+    let mut best: Option<(Version, &Release)> = None;
+    for release in &feed.releases {
+        let Some(version) = Version::parse(&release.version) else {
+            if verbose {
+                eprintln!("⚠️  忽略版本号无效的发布: {}", release.version);
+            }
+            continue;
+        };
+        if !channel.accepts(&release.channel) {
+            continue;
+        }
+        if installed.is_some_and(|installed| version <= *installed) {
+            continue;
+        }
+        if best.as_ref().is_none_or(|(current, _)| version > *current) {
+            best = Some((version, release));
+        }
+    }
+
+    let update = best.map(|(_, release)| {
+        let mut release = release.clone();
+        release.deltas.retain(|delta| {
+            let from = Version::parse(&delta.from);
+            from.is_some() && from.as_ref() == installed
+        });
+        release.deltas.truncate(1);
+        release
+    });
+
+    UpdateCheck {
+        update_available: update.is_some(),
+        installed_version: installed.map(Version::to_string),
+        channel,
+        update,
+    }
+}
+
+/// 解析相对地址：发布源为 URL 时相对于其所在目录，为本地文件时相对于文件所在目录
+fn resolve_url(source: &str, url: &str) -> String {
+    if is_url(url) {
+        return url.to_string();
+    }
+
+    if is_url(source) {
+        let base = source.split(['?', '#']).next().unwrap_or(source);
+        let base = base.rsplit_once('/').map_or(base, |(base, _)| base);
+        if let Some(path) = url.strip_prefix('/') {
+            // 以 `/` 开头时相对于站点根目录
+            let scheme_end = base.find("://").map_or(0, |i| i + 3);
+            let host_end = base[scheme_end..]
+                .find('/')
+                .map_or(base.len(), |i| scheme_end + i);
+            return format!("{}/{}", &base[..host_end], path);
+        }
+        format!("{}/{}", base, url.trim_start_matches("./"))
+    } else {
+        Path::new(source)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(url)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
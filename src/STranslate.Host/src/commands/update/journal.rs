@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::pending::PendingEntry;
+use super::transaction::{Operation, UpdatePhase};
+
+/// 更新日志文件名（位于更新工作目录中）。
+///
+/// 主程序通过该文件是否存在判断程序目录是否处于更新未完成的不一致状态，
+/// 与 `Constant.UpdateJournalFileName` 保持一致。
+pub const JOURNAL_FILE_NAME: &str = "journal.jsonl";
+
+/// 日志记录，每行一条 JSON
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum JournalRecord {
+    #[serde(rename_all = "camelCase")]
+    Begin {
+        archive: PathBuf,
+        started_at: String,
+    },
+    /// 进入新的阶段
+    Phase { phase: UpdatePhase },
+    /// 计划安装的文件（暂存目录中的相对路径）
+    Planned { files: Vec<String> },
+    /// 文件系统操作，在执行之前写入，回滚时允许其尚未真正执行
+    Step { operation: Operation },
+    /// 因占用而延迟到下次启动时处理的条目
+    Deferred { entry: PendingEntry },
+}
+
+/// 只追加的更新日志，每条记录写入后立即落盘，断电后可据此继续或回滚
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+        Ok(Self { file })
+    }
+
+    /// 打开已有日志继续追加
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    pub fn append(&mut self, record: &JournalRecord) -> io::Result<()> {
+        let mut line = serde_json::to_string(record).map_err(io::Error::other)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()
+    }
+
+    /// 读取日志中的全部记录；最后一行写入中途断电导致不完整时忽略该行
+    pub fn read(path: &Path) -> Result<Vec<JournalRecord>, Box<dyn Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("读取更新日志失败: {} ({})", path.display(), e))?;
+        let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+
+        let mut records = Vec::with_capacity(lines.len());
+        for (index, line) in lines.iter().enumerate() {
+            match serde_json::from_str(line) {
+                Ok(record) => records.push(record),
+                Err(_) if index + 1 == lines.len() => break,
+                Err(e) => {
+                    return Err(format!(
+                        "更新日志第 {} 行损坏: {} ({})",
+                        index + 1,
+                        path.display(),
+                        e
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// 未完成更新的概要信息
+#[derive(Debug)]
+pub struct JournalSummary {
+    pub archive: Option<PathBuf>,
+    pub started_at: Option<String>,
+    /// 中断时所处的阶段
+    pub phase: UpdatePhase,
+    /// 计划安装的文件数
+    pub planned: usize,
+    /// 已记录的文件系统操作数
+    pub steps: usize,
+}
+
+impl JournalSummary {
+    pub fn from_records(records: &[JournalRecord]) -> Self {
+        let mut summary = Self {
+            archive: None,
+            started_at: None,
+            phase: UpdatePhase::Prepare,
+            planned: 0,
+            steps: 0,
+        };
+        for record in records {
+            match record {
+                JournalRecord::Begin {
+                    archive,
+                    started_at,
+                } => {
+                    summary.archive = Some(archive.clone());
+                    summary.started_at = Some(started_at.clone());
+                }
+                JournalRecord::Phase { phase } => summary.phase = *phase,
+                JournalRecord::Planned { files } => summary.planned = files.len(),
+                JournalRecord::Step { .. } => summary.steps += 1,
+                JournalRecord::Deferred { .. } => {}
+            }
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::update::pending::PendingAction;
+
+    fn records() -> Vec<JournalRecord> {
+        vec![
+            JournalRecord::Begin {
+                archive: PathBuf::from("update.zip"),
+                started_at: "2025-01-01T00:00:00+08:00".to_string(),
+            },
+            JournalRecord::Phase {
+                phase: UpdatePhase::Backup,
+            },
+            JournalRecord::Planned {
+                files: vec!["STranslate.exe".to_string(), "a.dll".to_string()],
+            },
+            JournalRecord::Step {
+                operation: Operation::CreatedDir(PathBuf::from("Plugins")),
+            },
+            JournalRecord::Deferred {
+                entry: PendingEntry {
+                    path: "a.dll".to_string(),
+                    action: PendingAction::Replace,
+                },
+            },
+            JournalRecord::Phase {
+                phase: UpdatePhase::Swap,
+            },
+            JournalRecord::Step {
+                operation: Operation::Installed(PathBuf::from("a.dll")),
+            },
+        ]
+    }
+
+    fn write_journal(path: &Path) {
+        let mut journal = Journal::create(path).unwrap();
+        for record in records() {
+            journal.append(&record).unwrap();
+        }
+    }
+
+    #[test]
+    fn records_survive_a_round_trip() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join(JOURNAL_FILE_NAME);
+        write_journal(&path);
+        // 继续追加的记录接在原有记录之后
+        Journal::open(&path)
+            .unwrap()
+            .append(&JournalRecord::Step {
+                operation: Operation::RemovedDir(PathBuf::from("old")),
+            })
+            .unwrap();
+
+        let read = Journal::read(&path).unwrap();
+        assert_eq!(read.len(), records().len() + 1);
+        let summary = JournalSummary::from_records(&read);
+        assert_eq!(summary.archive.as_deref(), Some(Path::new("update.zip")));
+        assert_eq!(summary.phase, UpdatePhase::Swap);
+        assert_eq!(summary.planned, 2);
+        assert_eq!(summary.steps, 3);
+    }
+
+    #[test]
+    fn create_discards_a_previous_journal() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join(JOURNAL_FILE_NAME);
+        write_journal(&path);
+
+        Journal::create(&path).unwrap();
+        assert!(Journal::read(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn torn_last_line_is_ignored() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join(JOURNAL_FILE_NAME);
+        write_journal(&path);
+        // 模拟写入最后一条记录时断电
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"type":"step","operation":{"installed":"b."#)
+            .unwrap();
+
+        let read = Journal::read(&path).unwrap();
+        assert_eq!(read.len(), records().len());
+        assert_eq!(JournalSummary::from_records(&read).steps, 2);
+    }
+
+    #[test]
+    fn corrupted_earlier_line_is_an_error() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join(JOURNAL_FILE_NAME);
+        write_journal(&path);
+        let content = fs::read_to_string(&path).unwrap();
+        let mut lines: Vec<&str> = content.lines().collect();
+        lines[1] = "{\"type\":";
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let error = Journal::read(&path).unwrap_err().to_string();
+        assert!(error.contains("第 2 行损坏"), "{}", error);
+    }
+
+    #[test]
+    fn journal_without_phase_records_is_in_prepare() {
+        let summary = JournalSummary::from_records(&records()[..1]);
+        assert_eq!(summary.phase, UpdatePhase::Prepare);
+        assert_eq!(summary.steps, 0);
+        assert!(summary.started_at.is_some());
+    }
+}
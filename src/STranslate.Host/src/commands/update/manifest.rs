@@ -0,0 +1,519 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use super::delta::DeltaManifest;
+use super::migration::SettingsMigration;
+use super::plugins::PluginCompatibility;
+use super::signature::{SignatureOptions, SignatureStatus, verify_manifest_signature};
+
+/// 内嵌在更新包根目录中的清单文件名
+pub const MANIFEST_FILE_NAME: &str = "update-manifest.json";
+
+/// 程序目录中记录当前已安装版本文件列表的文件名
+pub const INSTALLED_MANIFEST_NAME: &str = ".MANIFEST";
+
+/// 与压缩包并列存放的清单文件后缀，例如 `STranslate.zip.manifest.json`
+const SIDECAR_SUFFIX: &str = ".manifest.json";
+
+/// 更新包清单，列出包内每个文件的大小与 SHA-256
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageManifest {
+    #[serde(default)]
+    pub files: Vec<ManifestFile>,
+    /// `--clean` 时额外保留的路径模式
+    #[serde(default)]
+    pub preserve: Vec<String>,
+    /// 更新包版本，安装后记录在安装记录中用于下次更新时比较
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// 差分包信息，完整包中不存在
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<DeltaManifest>,
+    /// 新版本对用户插件的兼容性要求
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin_compatibility: Option<PluginCompatibility>,
+    /// 更新后对用户设置文件执行的迁移步骤
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub settings_migrations: Vec<SettingsMigration>,
+    /// 历来的预装插件 ID（含新版本已移除的），没有安装记录时据此识别需要移除的旧预装插件
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_plugin_ids: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestFile {
+    /// 以 `/` 分隔的相对路径
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl PackageManifest {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("读取清单失败: {} ({})", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("解析清单失败: {} ({})", path.display(), e).into())
+    }
+}
+
+impl PackageManifest {
+    /// 读取程序目录中的安装记录，不存在时返回 `None`
+    pub fn load_installed(install_dir: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        let path = install_dir.join(INSTALLED_MANIFEST_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Self::from_file(&path).map(Some)
+    }
+
+    /// 扫描目录生成清单（计算每个文件的大小与 SHA-256）
+    pub fn from_directory(root: &Path) -> io::Result<Self> {
+        let mut files = Vec::new();
+        for path in list_files(root)? {
+            let full_path = root.join(&path);
+            files.push(ManifestFile {
+                size: fs::metadata(&full_path)?.len(),
+                sha256: sha256_file(&full_path)?,
+                path,
+            });
+        }
+        Ok(Self {
+            files,
+            ..Default::default()
+        })
+    }
+
+    /// 安装记录只保留文件列表与版本
+    pub fn to_installed_record(&self) -> Self {
+        Self {
+            files: self.files.clone(),
+            version: self.version.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// 清单来源，用于输出报告
+#[derive(Debug)]
+pub enum ManifestSource {
+    /// 通过 `--manifest` 显式指定
+    Explicit(PathBuf),
+    /// 与压缩包并列存放
+    Sidecar(PathBuf),
+    /// 内嵌在更新包中
+    Embedded,
+}
+
+impl fmt::Display for ManifestSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestSource::Explicit(path) => write!(f, "指定文件 {}", path.display()),
+            ManifestSource::Sidecar(path) => write!(f, "同目录文件 {}", path.display()),
+            ManifestSource::Embedded => write!(f, "更新包内嵌 {}", MANIFEST_FILE_NAME),
+        }
+    }
+}
+
+/// 查找更新清单：外部清单（显式指定或同目录文件）通过签名校验时优先使用，否则使用内嵌清单；
+/// 没有内嵌清单时，只有更新包本身未签名才接受未签名的外部清单。
+/// 内嵌清单在暂存目录中找到后会被移除，不会安装到程序目录。
+pub fn locate_manifest(
+    explicit: Option<&Path>,
+    archive: &Path,
+    archive_signature: SignatureStatus,
+    staging_dir: &Path,
+    signature: &SignatureOptions,
+    verbose: bool,
+) -> Result<Option<(PackageManifest, ManifestSource)>, Box<dyn Error>> {
+    let embedded = staging_dir.join(MANIFEST_FILE_NAME);
+    let embedded_manifest = if embedded.is_file() {
+        let manifest = PackageManifest::from_file(&embedded)?;
+        fs::remove_file(&embedded)?;
+        Some(manifest)
+    } else {
+        None
+    };
+
+    let (path, source) = match explicit {
+        Some(path) => (
+            path.to_path_buf(),
+            ManifestSource::Explicit(path.to_path_buf()),
+        ),
+        None => {
+            let sidecar = sidecar_path(archive);
+            if !sidecar.is_file() {
+                return Ok(embedded_manifest.map(|manifest| (manifest, ManifestSource::Embedded)));
+            }
+            (sidecar.clone(), ManifestSource::Sidecar(sidecar))
+        }
+    };
+    let (manifest, status) = load_external(&path, signature, verbose)?;
+    if status == SignatureStatus::Verified {
+        return Ok(Some((manifest, source)));
+    }
+    if let Some(manifest) = embedded_manifest {
+        println!("⚠️  外部清单未签名，改用更新包内嵌清单: {}", path.display());
+        return Ok(Some((manifest, ManifestSource::Embedded)));
+    }
+    if archive_signature == SignatureStatus::Verified {
+        // 未签名的清单可以改写已签名更新包的版本、文件哈希与迁移步骤
+        if matches!(source, ManifestSource::Explicit(_)) {
+            return Err(format!("更新包已签名，不接受未签名的清单: {}", path.display()).into());
+        }
+        println!(
+            "⚠️  更新包已签名，忽略未签名的同目录清单: {}",
+            path.display()
+        );
+        return Ok(None);
+    }
+    Ok(Some((manifest, source)))
+}
+
+/// 读取外部清单并校验签名，解析的正是校验过的内容
+fn load_external(
+    path: &Path,
+    signature: &SignatureOptions,
+    verbose: bool,
+) -> Result<(PackageManifest, SignatureStatus), Box<dyn Error>> {
+    let content =
+        fs::read(path).map_err(|e| format!("读取清单失败: {} ({})", path.display(), e))?;
+    let status = verify_manifest_signature(path, &content, signature, verbose)?;
+    let manifest = serde_json::from_slice(&content)
+        .map_err(|e| format!("解析清单失败: {} ({})", path.display(), e))?;
+    Ok((manifest, status))
+}
+
+fn sidecar_path(archive: &Path) -> PathBuf {
+    let mut name = archive.file_name().unwrap_or_default().to_os_string();
+    name.push(SIDECAR_SUFFIX);
+    archive.with_file_name(name)
+}
+
+/// 清单校验失败的详细信息
+#[derive(Debug, Default)]
+pub struct ManifestMismatchError {
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+    pub mismatched: Vec<(String, String)>,
+}
+
+impl ManifestMismatchError {
+    fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+impl fmt::Display for ManifestMismatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "更新包与清单不一致，拒绝更新")?;
+        for path in &self.missing {
+            write!(f, "\n  - 缺失: {}", path)?;
+        }
+        for path in &self.extra {
+            write!(f, "\n  - 多余: {}", path)?;
+        }
+        for (path, reason) in &self.mismatched {
+            write!(f, "\n  - 不匹配: {} ({})", path, reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ManifestMismatchError {}
+
+/// 校验暂存目录中的文件与清单完全一致（无缺失、无多余、大小与哈希均匹配）
+pub fn verify_directory(manifest: &PackageManifest, root: &Path) -> Result<(), Box<dyn Error>> {
+    let mut expected: BTreeMap<&str, &ManifestFile> = BTreeMap::new();
+    for file in &manifest.files {
+        if expected.insert(file.path.as_str(), file).is_some() {
+            return Err(format!("清单中存在重复条目: {}", file.path).into());
+        }
+    }
+
+    let actual: BTreeSet<String> = list_files(root)?.into_iter().collect();
+    let mut error = ManifestMismatchError::default();
+
+    for (path, file) in &expected {
+        if !actual.contains(*path) {
+            error.missing.push(path.to_string());
+            continue;
+        }
+
+        let full_path = root.join(path);
+        let size = fs::metadata(&full_path)?.len();
+        if size != file.size {
+            error
+                .mismatched
+                .push((path.to_string(), format!("大小 {} ≠ {}", size, file.size)));
+            continue;
+        }
+
+        let hash = sha256_file(&full_path)?;
+        if !hash.eq_ignore_ascii_case(&file.sha256) {
+            error
+                .mismatched
+                .push((path.to_string(), "SHA-256 不匹配".to_string()));
+        }
+    }
+
+    for path in &actual {
+        if !expected.contains_key(path.as_str()) {
+            error.extra.push(path.clone());
+        }
+    }
+
+    if error.is_empty() {
+        Ok(())
+    } else {
+        Err(Box::new(error))
+    }
+}
+
+/// 计算文件的 SHA-256，返回小写十六进制字符串
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 递归列出目录下所有文件，返回以 `/` 分隔的相对路径
+pub fn list_files(root: &Path) -> io::Result<Vec<String>> {
+    fn walk(root: &Path, dir: &Path, files: &mut Vec<String>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                walk(root, &path, files)?;
+            } else {
+                files.push(to_manifest_path(path.strip_prefix(root).unwrap_or(&path)));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    if root.is_dir() {
+        walk(root, root, &mut files)?;
+    }
+    Ok(files)
+}
+
+/// 将相对路径转换为清单使用的 `/` 分隔形式
+pub fn to_manifest_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::update::signature::default_signature_path;
+
+    /// 签名测试文件：固定测试密钥 `test.pub` 签名的清单（版本 9.0.0）与更新包
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/signing")
+            .join(name)
+    }
+
+    struct Package {
+        _temp: tempfile::TempDir,
+        archive: PathBuf,
+        staging: PathBuf,
+    }
+
+    impl Package {
+        /// 带有内嵌清单（版本 1.0.0）的更新包
+        fn new() -> Self {
+            let temp = tempfile::tempdir().unwrap();
+            let archive = temp.path().join("update.zip");
+            let staging = temp.path().join("staging");
+            fs::write(&archive, b"archive").unwrap();
+            fs::create_dir_all(&staging).unwrap();
+            fs::write(
+                staging.join(MANIFEST_FILE_NAME),
+                r#"{"version":"1.0.0","files":[]}"#,
+            )
+            .unwrap();
+            Self {
+                _temp: temp,
+                archive,
+                staging,
+            }
+        }
+
+        fn without_embedded(self) -> Self {
+            fs::remove_file(self.staging.join(MANIFEST_FILE_NAME)).unwrap();
+            self
+        }
+
+        /// 放置同目录清单，`signed` 时一并放置签名
+        fn sidecar(&self, signed: bool) -> PathBuf {
+            let path = sidecar_path(&self.archive);
+            fs::copy(fixture("manifest.json"), &path).unwrap();
+            if signed {
+                fs::copy(
+                    fixture("manifest.json.minisig"),
+                    default_signature_path(&path),
+                )
+                .unwrap();
+            }
+            path
+        }
+
+        fn locate(
+            &self,
+            explicit: Option<&Path>,
+            archive_signature: SignatureStatus,
+        ) -> Result<Option<(PackageManifest, ManifestSource)>, Box<dyn Error>> {
+            let options = SignatureOptions {
+                public_key: Some(fixture("test.pub")),
+                ..Default::default()
+            };
+            locate_manifest(
+                explicit,
+                &self.archive,
+                archive_signature,
+                &self.staging,
+                &options,
+                false,
+            )
+        }
+    }
+
+    fn version(found: &Option<(PackageManifest, ManifestSource)>) -> Option<&str> {
+        found.as_ref().and_then(|(m, _)| m.version.as_deref())
+    }
+
+    #[test]
+    fn signed_external_manifest_wins_over_embedded() {
+        let package = Package::new();
+        package.sidecar(true);
+
+        let found = package.locate(None, SignatureStatus::Verified).unwrap();
+        assert_eq!(version(&found), Some("9.0.0"));
+        assert!(matches!(found.unwrap().1, ManifestSource::Sidecar(_)));
+        // 内嵌清单不会被安装到程序目录
+        assert!(!package.staging.join(MANIFEST_FILE_NAME).exists());
+    }
+
+    #[test]
+    fn unsigned_external_manifest_falls_back_to_embedded() {
+        let package = Package::new();
+        let sidecar = package.sidecar(false);
+
+        for explicit in [None, Some(sidecar.as_path())] {
+            fs::write(
+                package.staging.join(MANIFEST_FILE_NAME),
+                r#"{"version":"1.0.0"}"#,
+            )
+            .unwrap();
+            let found = package.locate(explicit, SignatureStatus::Verified).unwrap();
+            assert_eq!(version(&found), Some("1.0.0"));
+            assert!(matches!(found.unwrap().1, ManifestSource::Embedded));
+        }
+    }
+
+    #[test]
+    fn unsigned_external_manifest_is_rejected_for_signed_archive() {
+        let package = Package::new().without_embedded();
+        let sidecar = package.sidecar(false);
+
+        let error = package
+            .locate(Some(&sidecar), SignatureStatus::Verified)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("不接受未签名的清单"), "{}", error);
+        // 同目录清单只是被忽略
+        assert!(
+            package
+                .locate(None, SignatureStatus::Verified)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn unsigned_external_manifest_is_accepted_for_unsigned_archive() {
+        let package = Package::new().without_embedded();
+        package.sidecar(false);
+
+        let found = package.locate(None, SignatureStatus::Skipped).unwrap();
+        assert_eq!(version(&found), Some("9.0.0"));
+    }
+
+    #[test]
+    fn tampered_signed_manifest_is_an_error() {
+        let package = Package::new();
+        let sidecar = package.sidecar(true);
+        fs::write(&sidecar, r#"{"version":"99.0.0","files":[]}"#).unwrap();
+
+        assert!(package.locate(None, SignatureStatus::Verified).is_err());
+    }
+
+    #[test]
+    fn verify_directory_reports_every_difference() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        for (path, content) in [
+            ("same.txt", "same"),
+            ("sub/size.txt", "long"),
+            ("hash.txt", "abcd"),
+        ] {
+            fs::write(root.join(path), content).unwrap();
+        }
+        let mut manifest = PackageManifest::from_directory(root).unwrap();
+        assert!(verify_directory(&manifest, root).is_ok());
+
+        fs::write(root.join("sub/size.txt"), "longer").unwrap();
+        fs::write(root.join("hash.txt"), "wxyz").unwrap();
+        fs::write(root.join("extra.txt"), "extra").unwrap();
+        manifest.files.push(ManifestFile {
+            path: "missing.txt".to_string(),
+            size: 1,
+            sha256: String::new(),
+        });
+
+        let error = verify_directory(&manifest, root).unwrap_err();
+        let error = error.downcast_ref::<ManifestMismatchError>().unwrap();
+        assert_eq!(error.missing, ["missing.txt"]);
+        assert_eq!(error.extra, ["extra.txt"]);
+        let mismatched: Vec<&str> = error.mismatched.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(mismatched, ["hash.txt", "sub/size.txt"]);
+    }
+
+    #[test]
+    fn duplicate_manifest_entries_are_rejected() {
+        let temp = tempfile::tempdir().unwrap();
+        fs::write(temp.path().join("a.txt"), "a").unwrap();
+        let mut manifest = PackageManifest::from_directory(temp.path()).unwrap();
+        manifest.files.push(manifest.files[0].clone());
+
+        assert!(verify_directory(&manifest, temp.path()).is_err());
+    }
+}
@@ -0,0 +1,637 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::version::Version;
+
+/// 设置文件所在目录相对数据目录的名称，与 `Constant.Settings` 保持一致
+pub const SETTINGS_DIR_NAME: &str = "Settings";
+
+/// 设置迁移步骤，按清单中的顺序执行
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsMigration {
+    /// 步骤标识，用于报告
+    pub id: String,
+    /// 更新前的版本不低于此版本时才执行
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_version: Option<String>,
+    /// 更新前的版本低于此版本时才执行
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_version: Option<String>,
+    #[serde(flatten)]
+    pub action: MigrationAction,
+}
+
+/// 迁移操作。文件路径相对于设置目录，键以 `.` 分隔表示嵌套对象
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(
+    tag = "action",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum MigrationAction {
+    /// 重命名键，目标键已存在时不覆盖
+    Rename {
+        file: String,
+        from: String,
+        to: String,
+    },
+    /// 将键移动到另一个文件，目标键默认与原键相同
+    Move {
+        file: String,
+        key: String,
+        to_file: String,
+        #[serde(default)]
+        to_key: Option<String>,
+    },
+    /// 键不存在时写入默认值
+    SetDefault {
+        file: String,
+        key: String,
+        value: Value,
+    },
+    /// 删除键
+    Drop { file: String, key: String },
+}
+
+impl fmt::Display for MigrationAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationAction::Rename { file, from, to } => {
+                write!(f, "{}: 重命名 {} → {}", file, from, to)
+            }
+            MigrationAction::Move {
+                file,
+                key,
+                to_file,
+                to_key,
+            } => write!(
+                f,
+                "{}: 移动 {} → {}: {}",
+                file,
+                key,
+                to_file,
+                to_key.as_deref().unwrap_or(key)
+            ),
+            MigrationAction::SetDefault { file, key, .. } => {
+                write!(f, "{}: 设置默认值 {}", file, key)
+            }
+            MigrationAction::Drop { file, key } => write!(f, "{}: 删除 {}", file, key),
+        }
+    }
+}
+
+/// 设置迁移的执行结果：修改后的文件内容先保存在内存中，更新提交后再写入
+#[derive(Debug, Default)]
+pub struct MigrationPlan {
+    pub settings_dir: PathBuf,
+    /// 实际修改了设置的步骤
+    pub applied: Vec<String>,
+    /// 版本范围不匹配或无需修改而跳过的步骤
+    pub skipped: Vec<String>,
+    documents: BTreeMap<String, Document>,
+}
+
+#[derive(Debug)]
+struct Document {
+    /// 文件原本是否存在
+    existed: bool,
+    value: Value,
+    changed: bool,
+}
+
+impl MigrationPlan {
+    /// 需要写入的设置文件
+    pub fn changed_files(&self) -> Vec<&str> {
+        self.documents
+            .iter()
+            .filter(|(_, document)| document.changed)
+            .map(|(file, _)| file.as_str())
+            .collect()
+    }
+
+    /// 将修改后的设置写回磁盘，原文件先按主程序的备份命名规则复制一份；
+    /// 任一文件备份或写入失败时还原已写入的文件，并删除本次创建的备份副本
+    pub fn write(&self, verbose: bool) -> Result<(), Box<dyn Error>> {
+        let timestamp = Local::now().format("%Y-%m-%d-%H-%M-%S-%3f").to_string();
+        let mut written: Vec<(PathBuf, Option<PathBuf>)> = Vec::new();
+
+        for file in self.changed_files() {
+            let document = &self.documents[file];
+            let path = self.settings_dir.join(file);
+            let backup = document.existed.then(|| backup_path(&path, &timestamp));
+            let result = match &backup {
+                Some(backup) => fs::copy(&path, backup)
+                    .map(|_| ())
+                    .map_err(|e| format!("备份设置文件失败: {} ({})", path.display(), e)),
+                None => Ok(()),
+            }
+            .and_then(|()| {
+                write_json(&path, &document.value)
+                    .map_err(|e| format!("写入设置文件失败: {} ({})", path.display(), e))
+            });
+
+            if let Err(e) = result {
+                // 当前文件未被改动，备份副本（可能只复制了一部分）直接删除
+                if let Some(backup) = &backup {
+                    let _ = fs::remove_file(backup);
+                }
+                restore(&written);
+                return Err(e.into());
+            }
+            if verbose && let Some(backup) = &backup {
+                println!("🗄️  已备份设置: {}", backup.display());
+            }
+            written.push((path, backup));
+        }
+        Ok(())
+    }
+}
+
+/// 按逆序还原已写入的设置文件；还原成功后删除备份副本，还原失败时保留备份供手动恢复
+fn restore(written: &[(PathBuf, Option<PathBuf>)]) {
+    for (path, backup) in written.iter().rev() {
+        match backup {
+            Some(backup) => {
+                if fs::copy(backup, path).is_ok() {
+                    let _ = fs::remove_file(backup);
+                }
+            }
+            None => {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// 按顺序在内存中执行迁移步骤；`installed` 为更新前的版本，未知时跳过限定了版本范围的步骤
+pub fn plan_migrations(
+    settings_dir: &Path,
+    steps: &[SettingsMigration],
+    installed: Option<&Version>,
+) -> Result<MigrationPlan, Box<dyn Error>> {
+    let mut plan = MigrationPlan {
+        settings_dir: settings_dir.to_path_buf(),
+        ..Default::default()
+    };
+
+    for step in steps {
+        let label = format!("{} ({})", step.id, step.action);
+        if !in_range(step, installed)? {
+            plan.skipped.push(format!("{}: 版本范围不匹配", label));
+            continue;
+        }
+        if plan.apply(&step.action)? {
+            plan.applied.push(label);
+        } else {
+            plan.skipped.push(format!("{}: 无需修改", label));
+        }
+    }
+    Ok(plan)
+}
+
+impl MigrationPlan {
+    /// 执行一个迁移操作，返回是否修改了设置
+    fn apply(&mut self, action: &MigrationAction) -> Result<bool, Box<dyn Error>> {
+        match action {
+            MigrationAction::Rename { file, from, to } => {
+                let Some(document) = self.document(file)? else {
+                    return Ok(false);
+                };
+                if get(&document.value, to).is_some() {
+                    return Ok(false);
+                }
+                let Some(value) = remove(&mut document.value, from) else {
+                    return Ok(false);
+                };
+                set(&mut document.value, to, value)?;
+                document.changed = true;
+                Ok(true)
+            }
+            MigrationAction::Move {
+                file,
+                key,
+                to_file,
+                to_key,
+            } => {
+                let to_key = to_key.as_deref().unwrap_or(key);
+                let Some(value) = self
+                    .document(file)?
+                    .and_then(|document| get(&document.value, key).cloned())
+                else {
+                    return Ok(false);
+                };
+
+                // 目标文件不存在时新建，主程序读取时会补全其余默认值
+                let target = self.document_or_new(to_file)?;
+                if get(&target.value, to_key).is_some() {
+                    return Ok(false);
+                }
+                set(&mut target.value, to_key, value)?;
+                target.changed = true;
+
+                let Some(source) = self.document(file)? else {
+                    return Err(format!("迁移步骤中的设置文件已不存在: {}", file).into());
+                };
+                remove(&mut source.value, key);
+                source.changed = true;
+                Ok(true)
+            }
+            MigrationAction::SetDefault { file, key, value } => {
+                // 设置文件不存在时主程序会使用全部默认值，无需创建
+                let Some(document) = self.document(file)? else {
+                    return Ok(false);
+                };
+                if get(&document.value, key).is_some() {
+                    return Ok(false);
+                }
+                set(&mut document.value, key, value.clone())?;
+                document.changed = true;
+                Ok(true)
+            }
+            MigrationAction::Drop { file, key } => {
+                let Some(document) = self.document(file)? else {
+                    return Ok(false);
+                };
+                if remove(&mut document.value, key).is_none() {
+                    return Ok(false);
+                }
+                document.changed = true;
+                Ok(true)
+            }
+        }
+    }
+
+    /// 读取设置文件（已读取时返回内存中的内容），文件不存在时返回 `None`
+    fn document(&mut self, file: &str) -> Result<Option<&mut Document>, Box<dyn Error>> {
+        if !self.documents.contains_key(file) {
+            let path = settings_path(&self.settings_dir, file)?;
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => {
+                    return Err(format!("读取设置文件失败: {} ({})", path.display(), e).into());
+                }
+            };
+            let value = serde_json::from_str(content.trim_start_matches('\u{feff}'))
+                .map_err(|e| format!("解析设置文件失败: {} ({})", path.display(), e))?;
+            self.documents.insert(
+                file.to_string(),
+                Document {
+                    existed: true,
+                    value,
+                    changed: false,
+                },
+            );
+        }
+        Ok(self.documents.get_mut(file))
+    }
+
+    fn document_or_new(&mut self, file: &str) -> Result<&mut Document, Box<dyn Error>> {
+        self.document(file)?;
+        Ok(self
+            .documents
+            .entry(file.to_string())
+            .or_insert_with(|| Document {
+                existed: false,
+                value: Value::Object(Map::new()),
+                changed: false,
+            }))
+    }
+}
+
+fn in_range(step: &SettingsMigration, installed: Option<&Version>) -> Result<bool, Box<dyn Error>> {
+    let parse = |text: &Option<String>| -> Result<Option<Version>, Box<dyn Error>> {
+        text.as_deref()
+            .map(|text| {
+                Version::parse(text)
+                    .ok_or_else(|| format!("迁移步骤 {} 的版本号无效: {}", step.id, text).into())
+            })
+            .transpose()
+    };
+    let from = parse(&step.from_version)?;
+    let to = parse(&step.to_version)?;
+    if from.is_none() && to.is_none() {
+        return Ok(true);
+    }
+
+    let Some(installed) = installed else {
+        return Ok(false);
+    };
+    Ok(from.is_none_or(|from| *installed >= from) && to.is_none_or(|to| *installed < to))
+}
+
+/// 设置文件必须位于设置目录之内
+fn settings_path(settings_dir: &Path, file: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let relative = Path::new(file);
+    if relative
+        .components()
+        .any(|c| !matches!(c, std::path::Component::Normal(_)))
+    {
+        return Err(format!("迁移步骤中的设置文件路径无效: {}", file).into());
+    }
+    Ok(settings_dir.join(relative))
+}
+
+/// 与主程序 `StorageBase.BackupOriginFile` 相同的命名：`<文件名>-<时间>.json`
+fn backup_path(path: &Path, timestamp: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    path.with_file_name(format!("{}-{}{}", stem, timestamp, extension))
+}
+
+/// 先写入临时文件再重命名，避免写到一半时留下损坏的设置文件
+fn write_json(path: &Path, value: &Value) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let result = fs::write(&temp, serde_json::to_string_pretty(value)?)
+        .and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+fn get<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.')
+        .try_fold(value, |current, part| current.get(part))
+}
+
+fn remove(value: &mut Value, key: &str) -> Option<Value> {
+    let (parent, last) = match key.rsplit_once('.') {
+        Some((parent, last)) => (parent_mut(value, parent)?, last),
+        None => (value, key),
+    };
+    parent.as_object_mut()?.shift_remove(last)
+}
+
+/// 写入键，缺少的上级对象自动创建
+fn set(value: &mut Value, key: &str, new_value: Value) -> Result<(), Box<dyn Error>> {
+    let mut current = value;
+    let mut parts = key.split('.').peekable();
+    while let Some(part) = parts.next() {
+        let object = current
+            .as_object_mut()
+            .ok_or_else(|| format!("无法写入 {}: 上级不是对象", key))?;
+        if parts.peek().is_none() {
+            object.insert(part.to_string(), new_value);
+            return Ok(());
+        }
+        current = object
+            .entry(part)
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    Ok(())
+}
+
+fn parent_mut<'a>(value: &'a mut Value, key: &str) -> Option<&'a mut Value> {
+    key.split('.')
+        .try_fold(value, |current, part| current.get_mut(part))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn step(
+        id: &str,
+        range: (Option<&str>, Option<&str>),
+        action: MigrationAction,
+    ) -> SettingsMigration {
+        SettingsMigration {
+            id: id.to_string(),
+            from_version: range.0.map(str::to_string),
+            to_version: range.1.map(str::to_string),
+            action,
+        }
+    }
+
+    fn any_version(id: &str, action: MigrationAction) -> SettingsMigration {
+        step(id, (None, None), action)
+    }
+
+    fn settings(files: &[(&str, Value)]) -> tempfile::TempDir {
+        let temp = tempfile::tempdir().unwrap();
+        for (file, value) in files {
+            fs::write(temp.path().join(file), value.to_string()).unwrap();
+        }
+        temp
+    }
+
+    fn read(dir: &Path, file: &str) -> Value {
+        serde_json::from_str(&fs::read_to_string(dir.join(file)).unwrap()).unwrap()
+    }
+
+    /// 设置目录中的文件名，按名称排序
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn dotted_keys_address_nested_objects() {
+        let mut value = json!({"a": {"b": {"c": 1}}, "top": 2});
+        assert_eq!(get(&value, "a.b.c"), Some(&json!(1)));
+        assert_eq!(get(&value, "a.x.c"), None);
+
+        set(&mut value, "a.new.deep", json!(true)).unwrap();
+        assert_eq!(get(&value, "a.new.deep"), Some(&json!(true)));
+        // 上级不是对象时不能写入
+        assert!(set(&mut value, "top.child", json!(3)).is_err());
+
+        assert_eq!(remove(&mut value, "a.b.c"), Some(json!(1)));
+        assert_eq!(remove(&mut value, "a.b.c"), None);
+        assert_eq!(remove(&mut value, "top"), Some(json!(2)));
+        assert_eq!(value, json!({"a": {"b": {}, "new": {"deep": true}}}));
+    }
+
+    #[test]
+    fn in_range_compares_with_the_installed_version() {
+        let check = |from: Option<&str>, to: Option<&str>, installed: Option<&str>| {
+            let installed = installed.map(|text| Version::parse(text).unwrap());
+            let action = MigrationAction::Drop {
+                file: "a.json".to_string(),
+                key: "k".to_string(),
+            };
+            in_range(&step("s", (from, to), action), installed.as_ref()).unwrap()
+        };
+
+        assert!(check(None, None, None));
+        assert!(check(Some("1.0.0"), Some("2.0.0"), Some("1.0.0")));
+        assert!(check(Some("1.0.0"), Some("2.0.0"), Some("1.9.9")));
+        assert!(!check(Some("1.0.0"), Some("2.0.0"), Some("2.0.0")));
+        assert!(!check(Some("1.0.0"), None, Some("0.9.0")));
+        assert!(check(None, Some("2.0.0"), Some("0.1.0")));
+        // 版本未知时跳过限定了版本范围的步骤
+        assert!(!check(None, Some("2.0.0"), None));
+
+        let invalid = step(
+            "bad",
+            (Some("x.y"), None),
+            MigrationAction::Drop {
+                file: "a.json".to_string(),
+                key: "k".to_string(),
+            },
+        );
+        assert!(in_range(&invalid, None).is_err());
+    }
+
+    #[test]
+    fn plan_applies_steps_in_order_and_writes_changed_files() {
+        let dir = settings(&[
+            (
+                "General.json",
+                json!({"Old": 1, "Nested": {"Drop": true}, "Moving": "x"}),
+            ),
+            ("Unchanged.json", json!({"Keep": 1})),
+        ]);
+        let steps = [
+            any_version(
+                "rename",
+                MigrationAction::Rename {
+                    file: "General.json".to_string(),
+                    from: "Old".to_string(),
+                    to: "Group.New".to_string(),
+                },
+            ),
+            any_version(
+                "move",
+                MigrationAction::Move {
+                    file: "General.json".to_string(),
+                    key: "Moving".to_string(),
+                    to_file: "Other.json".to_string(),
+                    to_key: Some("Moved".to_string()),
+                },
+            ),
+            any_version(
+                "default",
+                MigrationAction::SetDefault {
+                    file: "General.json".to_string(),
+                    key: "Group.New".to_string(),
+                    value: json!(99),
+                },
+            ),
+            any_version(
+                "drop",
+                MigrationAction::Drop {
+                    file: "General.json".to_string(),
+                    key: "Nested.Drop".to_string(),
+                },
+            ),
+            any_version(
+                "missing",
+                MigrationAction::SetDefault {
+                    file: "Missing.json".to_string(),
+                    key: "A".to_string(),
+                    value: json!(1),
+                },
+            ),
+            step(
+                "old",
+                (None, Some("1.0.0")),
+                MigrationAction::Drop {
+                    file: "Unchanged.json".to_string(),
+                    key: "Keep".to_string(),
+                },
+            ),
+        ];
+
+        let installed = Version::parse("1.5.0").unwrap();
+        let plan = plan_migrations(dir.path(), &steps, Some(&installed)).unwrap();
+        let ids = |labels: &[String]| -> Vec<String> {
+            labels
+                .iter()
+                .map(|label| label.split(' ').next().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(ids(&plan.applied), ["rename", "move", "drop"]);
+        assert_eq!(ids(&plan.skipped), ["default", "missing", "old"]);
+        assert_eq!(plan.changed_files(), ["General.json", "Other.json"]);
+
+        plan.write(false).unwrap();
+        assert_eq!(
+            read(dir.path(), "General.json"),
+            json!({"Nested": {}, "Group": {"New": 1}})
+        );
+        assert_eq!(read(dir.path(), "Other.json"), json!({"Moved": "x"}));
+        assert_eq!(read(dir.path(), "Unchanged.json"), json!({"Keep": 1}));
+        // 只有原本存在的文件才有备份
+        let names = file_names(dir.path());
+        assert_eq!(names.len(), 4);
+        assert!(names[0].starts_with("General-") && names[0].ends_with(".json"));
+    }
+
+    #[test]
+    fn move_does_not_overwrite_an_existing_target() {
+        let dir = settings(&[
+            ("A.json", json!({"Key": "new"})),
+            ("B.json", json!({"Key": "mine"})),
+        ]);
+        let steps = [any_version(
+            "move",
+            MigrationAction::Move {
+                file: "A.json".to_string(),
+                key: "Key".to_string(),
+                to_file: "B.json".to_string(),
+                to_key: None,
+            },
+        )];
+
+        let plan = plan_migrations(dir.path(), &steps, None).unwrap();
+        assert!(plan.applied.is_empty());
+        assert!(plan.changed_files().is_empty());
+    }
+
+    #[test]
+    fn settings_files_must_stay_inside_the_settings_dir() {
+        let dir = settings(&[]);
+        let steps = [any_version(
+            "escape",
+            MigrationAction::Drop {
+                file: "../outside.json".to_string(),
+                key: "Key".to_string(),
+            },
+        )];
+        assert!(plan_migrations(dir.path(), &steps, None).is_err());
+    }
+
+    #[test]
+    fn failed_write_restores_files_and_removes_backups() {
+        let dir = settings(&[("A.json", json!({"Key": 1}))]);
+        let steps = [any_version(
+            "move",
+            MigrationAction::Move {
+                file: "A.json".to_string(),
+                key: "Key".to_string(),
+                to_file: "Z.json".to_string(),
+                to_key: None,
+            },
+        )];
+        let plan = plan_migrations(dir.path(), &steps, None).unwrap();
+        // A.json 先写入，随后 Z.json 因同名目录挡路而写入失败
+        fs::create_dir_all(dir.path().join("Z.json").join("blocker")).unwrap();
+
+        assert!(plan.write(false).is_err());
+        assert_eq!(read(dir.path(), "A.json"), json!({"Key": 1}));
+        assert_eq!(file_names(dir.path()), ["A.json", "Z.json"]);
+    }
+}
@@ -0,0 +1,697 @@
+mod delta;
+mod download;
+mod feed;
+mod journal;
+mod manifest;
+mod migration;
+mod pending;
+mod plugins;
+mod preserve;
+mod prune;
+mod relaunch;
+mod report;
+mod retry;
+mod self_update;
+mod signature;
+mod transaction;
+mod version;
+
+pub use feed::Channel;
+pub use relaunch::{LaunchPrivilege, SKIP_UAC_TASK_NAME};
+pub use self_update::finish_host_update_on_launch;
+
+use clap::{ArgMatches, ValueEnum};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::archive::extract::StripComponents;
+use crate::archive::format::ArchiveFormat;
+use crate::process::{ProcessTarget, ShutdownOptions, SystemProcesses, resolve_targets, shutdown};
+use crate::proxy::{ProxyConfig, ProxyOptions};
+use delta::DeltaBaseMismatch;
+use download::{CACHE_DIR_NAME, DownloadOptions, download_package, is_url};
+use feed::{check_update, load_feed};
+use migration::SETTINGS_DIR_NAME;
+use pending::{PendingOutcome, apply_pending};
+use plugins::{PLUGINS_DIR_NAME, mark_disabled};
+use preserve::default_data_dir;
+use relaunch::{RelaunchOptions, relaunch};
+use retry::RetryPolicy;
+use self_update::{HostUpdate, finish_host_update};
+use signature::{SignatureError, SignatureOptions, default_signature_path};
+use transaction::{UpdateError, UpdatePhase, UpdateTransaction};
+use version::{Version, VersionError, installed_version};
+
+/// 签名校验失败时的退出码，便于调用方与普通更新失败区分
+pub const EXIT_CODE_SIGNATURE: i32 = 3;
+
+/// 拒绝降级或同版本重新安装时的退出码
+pub const EXIT_CODE_VERSION: i32 = 4;
+
+/// 差分包与当前安装不匹配且未提供完整更新包时的退出码，调用方可据此改为下载完整包
+pub const EXIT_CODE_DELTA_BASE: i32 = 5;
+
+/// 根据更新错误类型返回进程退出码
+pub fn update_exit_code(error: &(dyn Error + 'static)) -> i32 {
+    let error = match error.downcast_ref::<UpdateError>() {
+        Some(update_error) => update_error.source.as_ref(),
+        None => error,
+    };
+
+    if error.is::<SignatureError>() {
+        EXIT_CODE_SIGNATURE
+    } else if error.is::<VersionError>() {
+        EXIT_CODE_VERSION
+    } else if error.is::<DeltaBaseMismatch>() {
+        EXIT_CODE_DELTA_BASE
+    } else {
+        1
+    }
+}
+
+/// 发现上次未完成的更新时的处理方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RecoverMode {
+    /// 已进入替换阶段时继续完成，否则回滚
+    Auto,
+    /// 继续完成上次的更新
+    Finish,
+    /// 回滚到更新前的状态
+    Rollback,
+}
+
+/// 更新事务的可选行为
+#[derive(Default)]
+pub struct UpdateOptions {
+    /// 清理保留规则之外的文件
+    pub clean: bool,
+    /// 通过命令行追加的保留规则
+    pub preserve: Vec<String>,
+    /// 当前使用的数据目录，清理时始终保留
+    pub data_dir: Option<PathBuf>,
+    /// 显式指定的清单文件
+    pub manifest: Option<PathBuf>,
+    /// 找不到清单时拒绝更新
+    pub require_manifest: bool,
+    /// 允许安装低于当前版本的更新包
+    pub allow_downgrade: bool,
+    /// 允许重新安装相同版本
+    pub reinstall: bool,
+    /// 差分包无法应用时改用的完整更新包
+    pub fallback_archive: Option<PathBuf>,
+    /// 与主更新包在同一事务中安装的其他更新包（例如插件包）
+    pub extra_archives: Vec<PathBuf>,
+    /// 解压时去除的顶层目录层数
+    pub strip_components: StripComponents,
+    /// 用户插件目录，更新包声明了插件兼容性要求时检查其中的插件
+    pub plugins_dir: Option<PathBuf>,
+    /// 用户设置目录，更新包声明了设置迁移时在其中执行
+    pub settings_dir: Option<PathBuf>,
+    /// 未通过命令行 `--sha256` 校验的下载内容，须通过签名校验才允许安装
+    pub unverified_downloads: Vec<PathBuf>,
+    /// 允许安装既未通过 `--sha256` 校验也未通过签名校验的下载内容
+    pub allow_unverified: bool,
+    /// 更新包与外部清单的签名校验选项
+    pub signature: SignatureOptions,
+    /// 只校验更新包并报告插件兼容性与设置迁移，不修改程序目录与设置文件
+    pub dry_run: bool,
+}
+
+pub fn handle_update_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    // 已关闭程序且程序目录完好（未改动或已回滚）时记录程序目录，更新失败后重新启动原有程序
+    let mut restart_dir = None;
+    let result = run_update(matches, &mut restart_dir);
+    if result.is_err() && matches.get_flag("auto-start") {
+        match restart_dir {
+            Some(install_dir) => {
+                println!("🔁 更新未完成，重新启动原有程序");
+                let verbose = matches.get_flag("verbose");
+                if let Err(e) = relaunch(&install_dir, &relaunch_options(matches), verbose) {
+                    println!("⚠️  自动启动失败: {}", e);
+                }
+            }
+            None => println!("⏭️  更新未完成，跳过自动启动"),
+        }
+    }
+    result
+}
+
+fn run_update(
+    matches: &ArgMatches,
+    restart_dir: &mut Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let archive_paths: Vec<&String> = matches.get_many::<String>("archive").unwrap().collect();
+    let archive_path = archive_paths[0];
+    let wait_time = *matches.get_one::<u64>("wait-time").unwrap();
+    let should_clean = matches.get_flag("clean");
+    let process_name = matches.get_one::<String>("process-name");
+    let auto_start = matches.get_flag("auto-start");
+    let verbose = matches.get_flag("verbose");
+    let mut options = UpdateOptions {
+        clean: should_clean,
+        preserve: matches
+            .get_many::<String>("preserve")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        data_dir: matches.get_one::<String>("data-dir").map(PathBuf::from),
+        manifest: matches.get_one::<String>("manifest").map(PathBuf::from),
+        require_manifest: matches.get_flag("require-manifest"),
+        allow_downgrade: matches.get_flag("allow-downgrade"),
+        reinstall: matches.get_flag("reinstall"),
+        fallback_archive: None,
+        extra_archives: Vec::new(),
+        strip_components: *matches
+            .get_one::<StripComponents>("strip-components")
+            .unwrap(),
+        plugins_dir: None,
+        settings_dir: None,
+        unverified_downloads: Vec::new(),
+        allow_unverified: matches.get_flag("allow-unverified"),
+        signature: SignatureOptions {
+            signature: matches.get_one::<String>("signature").map(PathBuf::from),
+            public_key: matches.get_one::<String>("public-key").map(PathBuf::from),
+            required: matches.get_flag("require-signature"),
+        },
+        dry_run: matches.get_flag("dry-run"),
+    };
+
+    if verbose {
+        println!("🔧 开始更新程序...");
+        println!("   压缩包路径: {}", archive_path);
+        for extra in &archive_paths[1..] {
+            println!("   附加更新包: {}", extra);
+        }
+        if wait_time > 0 {
+            println!("   等待时间: {} 秒", wait_time);
+        }
+        println!("   清理目录: {}", should_clean);
+        println!("   自动启动: {}", auto_start);
+    }
+
+    // 更新日志中记录绝对路径，以便从任意工作目录恢复
+    let install_dir = std::path::absolute(resolve_install_dir(matches, archive_path)?)?;
+    let install_dir = install_dir.as_path();
+    let data_dir = options
+        .data_dir
+        .clone()
+        .or_else(|| default_data_dir(install_dir));
+    options.plugins_dir = match matches.get_one::<String>("plugins-dir") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => data_dir.as_ref().map(|dir| dir.join(PLUGINS_DIR_NAME)),
+    };
+    options.settings_dir = match matches.get_one::<String>("settings-dir") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => data_dir.as_ref().map(|dir| dir.join(SETTINGS_DIR_NAME)),
+    };
+
+    let download_options = DownloadOptions {
+        cache_dir: matches
+            .get_one::<String>("cache-dir")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| install_dir.join(CACHE_DIR_NAME)),
+        sha256: matches.get_one::<String>("sha256").cloned(),
+        timeout: Duration::from_secs(*matches.get_one::<u64>("download-timeout").unwrap()),
+        retries: *matches.get_one::<u32>("download-retries").unwrap(),
+        proxy: ProxyConfig::resolve(
+            &proxy_options(matches),
+            default_data_dir(install_dir).as_deref(),
+        )?,
+        verbose,
+    };
+    let mut downloaded = Vec::new();
+    let mut archives = Vec::new();
+    for (index, source) in archive_paths.iter().enumerate() {
+        let path = if is_url(source) {
+            // `--sha256` 只对应主更新包，附加更新包读取 <下载地址>.sha256
+            let download_options = DownloadOptions {
+                sha256: download_options.sha256.clone().filter(|_| index == 0),
+                ..download_options.clone()
+            };
+            let package = download_package(source, &download_options)?;
+            if !package.checksum_verified {
+                options.unverified_downloads.push(package.path.clone());
+            }
+            downloaded.push(package.path.clone());
+            package.path
+        } else {
+            PathBuf::from(source)
+        };
+        if !path.exists() {
+            return Err(format!("压缩包不存在: {}", source).into());
+        }
+        if ArchiveFormat::detect(&path)? == ArchiveFormat::Directory
+            && install_dir.starts_with(std::path::absolute(&path)?)
+        {
+            return Err(format!("更新目录不能包含程序目录: {}", path.display()).into());
+        }
+        archives.push(path);
+    }
+    let archive = archives.remove(0);
+    let archive = archive.as_path();
+    options.extra_archives = archives;
+
+    if let Some(fallback) = matches.get_one::<String>("fallback-archive") {
+        options.fallback_archive = Some(if is_url(fallback) {
+            // 完整包只在差分包无法应用时使用，但要在关闭程序之前准备好
+            let download_options = DownloadOptions {
+                sha256: matches.get_one::<String>("fallback-sha256").cloned(),
+                ..download_options
+            };
+            let package = download_package(fallback, &download_options)?;
+            if !package.checksum_verified {
+                options.unverified_downloads.push(package.path.clone());
+            }
+            downloaded.push(package.path.clone());
+            package.path
+        } else {
+            PathBuf::from(fallback)
+        });
+    }
+
+    if let Some(fallback) = &options.fallback_archive {
+        if !fallback.exists() {
+            return Err(format!("完整更新包不存在: {}", fallback.display()).into());
+        }
+        ArchiveFormat::detect(fallback)?;
+    }
+
+    let mut targets: Vec<ProcessTarget> = matches
+        .get_many::<u32>("pid")
+        .unwrap_or_default()
+        .map(|pid| ProcessTarget::Pid(*pid))
+        .collect();
+    targets.extend(
+        matches
+            .get_many::<String>("process-path")
+            .unwrap_or_default()
+            .map(|path| ProcessTarget::ExePath(PathBuf::from(path))),
+    );
+    if let Some(process) = process_name {
+        targets.push(ProcessTarget::Name {
+            name: process.clone(),
+            within: Some(install_dir.to_path_buf()),
+        });
+    }
+
+    let shutdown_options = ShutdownOptions {
+        timeout: Duration::from_secs(*matches.get_one::<u64>("shutdown-timeout").unwrap()),
+        verbose,
+        ..Default::default()
+    };
+    // 关闭程序并等待其释放文件，只执行一次
+    let mut processes_closed = false;
+    let mut close_once = |restart_dir: &mut Option<PathBuf>| -> Result<(), Box<dyn Error>> {
+        if processes_closed {
+            return Ok(());
+        }
+        processes_closed = true;
+        if !targets.is_empty() {
+            close_processes(&targets, &shutdown_options)?;
+            *restart_dir = Some(install_dir.to_path_buf());
+        }
+        if wait_time > 0 {
+            if verbose {
+                println!("⏳ 等待 {} 秒...", wait_time);
+            }
+            thread::sleep(Duration::from_secs(wait_time));
+        }
+        Ok(())
+    };
+
+    if UpdateTransaction::load_unfinished(install_dir, false)?.is_some() {
+        if options.dry_run {
+            // 试运行不触碰程序目录，准备工作目录时会清除上次未完成的更新日志
+            return Err("存在未完成的更新，请先执行 recover 后再试运行".into());
+        }
+        // 继续完成或回滚上次的更新会改动程序目录，需要先关闭程序
+        close_once(restart_dir)?;
+        recover_unfinished(install_dir, RecoverMode::Auto, verbose)?;
+    }
+
+    // 暂存与校验只写入工作目录，在关闭程序之前完成，拒绝更新时程序保持运行
+    let mut transaction = UpdateTransaction::new(install_dir, verbose);
+    let staged = match transaction.stage(archive, &options) {
+        Ok(staged) => staged,
+        Err(e) => {
+            if e.phase != UpdatePhase::Preflight {
+                transaction.discard();
+            }
+            return Err(e.into());
+        }
+    };
+    if options.dry_run {
+        transaction.commit().print_dry_run();
+        if auto_start {
+            println!("⏭️  试运行，跳过自动启动");
+        }
+        return Ok(());
+    }
+
+    close_once(restart_dir)?;
+    let previous = apply_pending(install_dir, RetryPolicy::default(), verbose)?;
+    if !previous.applied.is_empty() {
+        println!("✅ 已完成上次遗留的 {} 项延迟替换", previous.applied.len());
+    }
+
+    if let Err(e) = transaction.install(staged, &options) {
+        eprintln!("❌ 更新在「{}」阶段失败，正在回滚...", e.phase);
+        match transaction.rollback() {
+            Ok(()) => eprintln!("✅ 回滚完成，程序目录已恢复到更新前的状态"),
+            Err(rollback_error) => {
+                *restart_dir = None;
+                eprintln!("❌ 回滚未完全成功: {}", rollback_error);
+            }
+        }
+        return Err(e.into());
+    }
+    // 程序目录已更新，失败时不再启动原有程序
+    *restart_dir = None;
+    let mut report = transaction.commit();
+    if report.host_staged {
+        // 其余文件已经提交，宿主程序替换失败时与设置迁移失败一样只记录在报告中
+        report.host_update = Some(match finish_host_update(install_dir, verbose) {
+            Ok(update) => update,
+            Err(e) => {
+                println!("⚠️  宿主程序更新失败，继续使用原有宿主程序: {}", e);
+                HostUpdate::Failed(e.to_string())
+            }
+        });
+    }
+    report.print();
+    remove_downloaded(&downloaded, verbose);
+
+    if let Some(migration) = &report.settings_migration {
+        let files = migration.changed_files();
+        if files.is_empty() {
+            if verbose {
+                println!("ℹ️  设置文件无需迁移");
+            }
+        } else {
+            // 程序文件已替换完成，迁移失败时设置保持原样，由主程序按旧格式兼容处理
+            match migration.write(verbose) {
+                Ok(()) => println!("✅ 设置迁移完成: {} 个文件", files.len()),
+                Err(e) => println!("⚠️  设置迁移失败，设置文件保持不变: {}", e),
+            }
+        }
+    }
+
+    if matches.get_flag("disable-incompatible-plugins") {
+        for plugin in &report.incompatible_plugins {
+            match mark_disabled(&plugin.dir, &plugin.reason) {
+                Ok(()) => println!(
+                    "🧩 已停用不兼容插件，删除其目录中的 NeedDisable.txt 可重新启用: {}",
+                    plugin.name
+                ),
+                Err(e) => println!("⚠️  停用不兼容插件失败: {} ({})", plugin.dir.display(), e),
+            }
+        }
+    }
+
+    if !report.deferred.is_empty() {
+        // 占用可能只是暂时的（例如杀毒软件扫描），提交后再尝试一次
+        match apply_pending(install_dir, RetryPolicy::default(), verbose) {
+            Ok(outcome) if outcome.remaining.is_empty() => {
+                println!("✅ 延迟替换的文件已全部处理")
+            }
+            Ok(outcome) => println!(
+                "⏸️  {} 个文件仍被占用，将在下次启动时处理",
+                outcome.remaining.len()
+            ),
+            Err(e) => println!("⚠️  重试延迟替换失败，将在下次启动时处理: {}", e),
+        }
+    }
+
+    if verbose {
+        println!("✅ 解压完成");
+    }
+
+    if auto_start {
+        // 文件已经替换完成，启动失败不影响更新结果
+        if let Err(e) = relaunch(install_dir, &relaunch_options(matches), verbose) {
+            println!("⚠️  自动启动失败: {}", e);
+        }
+    }
+
+    println!("✅ 更新完成!");
+    Ok(())
+}
+
+/// 程序目录：优先使用 `--install-dir`（`--target`）；下载地址默认为宿主程序所在目录，本地压缩包默认为其上两级目录（`<程序目录>/tmp/xxx.zip`）
+fn resolve_install_dir(matches: &ArgMatches, archive: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(dir) = matches.get_one::<String>("install-dir") {
+        return Ok(PathBuf::from(dir));
+    }
+    let dir = if is_url(archive) {
+        std::env::current_exe()?.parent().map(Path::to_path_buf)
+    } else {
+        Path::new(archive)
+            .parent()
+            .and_then(|p| p.parent())
+            .map(Path::to_path_buf)
+    };
+    dir.ok_or_else(|| "无法确定程序目录".into())
+}
+
+fn relaunch_options(matches: &ArgMatches) -> RelaunchOptions {
+    RelaunchOptions {
+        exe: PathBuf::from(matches.get_one::<String>("launch-exe").unwrap()),
+        args: matches
+            .get_many::<String>("launch-arg")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        working_dir: matches.get_one::<String>("launch-dir").map(PathBuf::from),
+        privilege: *matches
+            .get_one::<LaunchPrivilege>("launch-privilege")
+            .unwrap(),
+        task_name: matches.get_one::<String>("launch-task").unwrap().clone(),
+    }
+}
+
+fn proxy_options(matches: &ArgMatches) -> ProxyOptions {
+    ProxyOptions {
+        proxy: matches.get_one::<String>("proxy").cloned(),
+        no_proxy: matches.get_flag("no-proxy"),
+        settings_file: matches
+            .get_one::<String>("settings-file")
+            .map(PathBuf::from),
+    }
+}
+
+/// 更新成功后删除下载的更新包及其签名，失败时保留以便重试
+fn remove_downloaded(paths: &[PathBuf], verbose: bool) {
+    for path in paths {
+        let _ = std::fs::remove_file(default_signature_path(path));
+        match std::fs::remove_file(path) {
+            Ok(()) if verbose => println!("🧹 已删除下载的更新包: {}", path.display()),
+            Ok(()) => {}
+            Err(e) => println!("⚠️  删除下载的更新包失败: {} ({})", path.display(), e),
+        }
+    }
+}
+
+pub fn handle_check_update_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let feed_source = matches.get_one::<String>("feed").unwrap();
+    let channel = *matches.get_one::<Channel>("channel").unwrap();
+    let verbose = matches.get_flag("verbose");
+    let timeout = Duration::from_secs(*matches.get_one::<u64>("timeout").unwrap());
+
+    let install_dir = match matches.get_one::<String>("install-dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::current_exe()?
+            .parent()
+            .ok_or("无法确定程序目录")?
+            .to_path_buf(),
+    };
+    let installed = match matches.get_one::<String>("current-version") {
+        Some(text) => Some(Version::parse(text).ok_or_else(|| format!("无效的版本号: {}", text))?),
+        None => installed_version(&install_dir)?,
+    };
+    let proxy = ProxyConfig::resolve(
+        &proxy_options(matches),
+        default_data_dir(&install_dir).as_deref(),
+    )?;
+
+    // 标准输出只包含 JSON，提示信息输出到标准错误
+    if verbose {
+        eprintln!("🔎 正在检查更新: {}", feed_source);
+        eprintln!(
+            "   已安装版本: {}",
+            installed
+                .as_ref()
+                .map_or("未知".to_string(), Version::to_string)
+        );
+    }
+
+    if verbose && is_url(feed_source) {
+        eprintln!("🌐 网络连接: {}", proxy.describe(feed_source)?);
+    }
+
+    let feed = load_feed(feed_source, &proxy, timeout)?;
+    let result = check_update(&feed, installed.as_ref(), channel, verbose);
+    let json = serde_json::to_string_pretty(&result)?;
+
+    match matches.get_one::<String>("output") {
+        Some(output) => {
+            std::fs::write(output, &json)
+                .map_err(|e| format!("写入检查结果失败: {} ({})", output, e))?;
+            if verbose {
+                eprintln!("📝 检查结果已写入: {}", output);
+            }
+        }
+        None => println!("{}", json),
+    }
+
+    if verbose {
+        match &result.update {
+            Some(release) => eprintln!("🆕 发现新版本: {}", release.version),
+            None => eprintln!("✅ 已是最新版本"),
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_recover_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let mode = *matches.get_one::<RecoverMode>("mode").unwrap();
+    let verbose = matches.get_flag("verbose");
+    let install_dir = match matches.get_one::<String>("install-dir") {
+        Some(dir) => std::path::absolute(dir)?,
+        None => std::env::current_exe()?
+            .parent()
+            .ok_or("无法确定程序目录")?
+            .to_path_buf(),
+    };
+
+    if !recover_unfinished(&install_dir, mode, verbose)? {
+        println!("✅ 没有未完成的更新");
+    }
+    Ok(())
+}
+
+/// 检测更新日志，继续完成或回滚上次中断的更新，返回是否存在未完成的更新
+fn recover_unfinished(
+    install_dir: &Path,
+    mode: RecoverMode,
+    verbose: bool,
+) -> Result<bool, Box<dyn Error>> {
+    let Some((mut transaction, summary)) =
+        UpdateTransaction::load_unfinished(install_dir, verbose)?
+    else {
+        return Ok(false);
+    };
+
+    println!("⚠️  检测到未完成的更新，中断于「{}」阶段", summary.phase);
+    if verbose {
+        if let Some(archive) = &summary.archive {
+            println!("   压缩包: {}", archive.display());
+        }
+        if let Some(started_at) = &summary.started_at {
+            println!("   开始时间: {}", started_at);
+        }
+        println!(
+            "   计划文件: {}，已记录操作: {}",
+            summary.planned, summary.steps
+        );
+    }
+
+    let can_finish = summary.phase == UpdatePhase::Swap;
+    let finish = match mode {
+        RecoverMode::Auto => can_finish,
+        RecoverMode::Finish if !can_finish => {
+            return Err("上次的更新尚未进入替换阶段，无法继续完成，只能回滚".into());
+        }
+        RecoverMode::Finish => true,
+        RecoverMode::Rollback => false,
+    };
+
+    if finish {
+        println!("🔄 正在继续完成上次的更新...");
+        if let Err(e) = transaction.finish() {
+            eprintln!("❌ 继续更新失败，正在回滚...");
+            transaction
+                .rollback()
+                .map_err(|rollback_error| format!("{}；回滚未完全成功: {}", e, rollback_error))?;
+            return Err(e.into());
+        }
+        transaction.commit().print();
+        println!("✅ 上次的更新已完成");
+    } else {
+        println!("🔄 正在回滚上次的更新...");
+        transaction.rollback()?;
+        println!("✅ 回滚完成，程序目录已恢复到更新前的状态");
+    }
+
+    Ok(true)
+}
+
+/// 按 PID 或可执行文件路径关闭进程：先请求正常退出，超时后才强制结束
+fn close_processes(
+    targets: &[ProcessTarget],
+    options: &ShutdownOptions,
+) -> Result<(), Box<dyn Error>> {
+    let control = SystemProcesses;
+    let processes = resolve_targets(&control, targets)?;
+
+    if processes.is_empty() {
+        if options.verbose {
+            println!("ℹ️  没有需要关闭的进程");
+        }
+        return Ok(());
+    }
+
+    if options.verbose {
+        println!("🔄 正在关闭 {} 个进程...", processes.len());
+    }
+    let report = shutdown(&control, &processes, options)?;
+
+    if !report.graceful.is_empty() {
+        println!("✅ 进程已正常退出: {:?}", report.graceful);
+    }
+    if !report.forced.is_empty() {
+        println!("⚠️  进程已被强制结束: {:?}", report.forced);
+    }
+    Ok(())
+}
+
+/// 应用上次更新遗留的延迟替换，主程序不经过 `start` 命令直接启动时由主程序调用
+pub fn handle_apply_pending_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let verbose = matches.get_flag("verbose");
+    let install_dir = match matches.get_one::<String>("install-dir") {
+        Some(dir) => std::path::absolute(dir)?,
+        None => std::env::current_exe()?
+            .parent()
+            .ok_or("无法确定程序目录")?
+            .to_path_buf(),
+    };
+
+    let outcome = apply_pending(&install_dir, RetryPolicy::ONCE, verbose)?;
+    report_pending(&outcome);
+    Ok(())
+}
+
+/// 启动程序前应用上次更新遗留的延迟替换，只尝试一次，避免拖慢启动
+pub fn apply_pending_on_launch(verbose: bool) {
+    let Some(install_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    else {
+        return;
+    };
+
+    match apply_pending(&install_dir, RetryPolicy::ONCE, verbose) {
+        Ok(outcome) => report_pending(&outcome),
+        Err(e) => println!("⚠️  应用延迟替换失败: {}", e),
+    }
+}
+
+fn report_pending(outcome: &PendingOutcome) {
+    if !outcome.applied.is_empty() {
+        println!("✅ 已完成 {} 项延迟替换", outcome.applied.len());
+    }
+    if !outcome.remaining.is_empty() {
+        println!("⏸️  {} 个文件仍被占用，稍后再试", outcome.remaining.len());
+    }
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::retry::{RetryPolicy, retry_io};
+
+/// 延迟替换目录名称（位于程序目录下），保存因占用而未能替换的新文件，与 `Constant.UpdatePendingFolderName` 保持一致
+pub const PENDING_DIR_NAME: &str = ".update-pending";
+
+/// 延迟替换列表文件名
+pub const PENDING_LIST_NAME: &str = "pending.json";
+/// 等待替换的新文件所在的子目录
+pub const PENDING_FILES_DIR: &str = "files";
+const OLD_DIR_NAME: &str = "old";
+
+/// 延迟到下次启动时执行的操作
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PendingAction {
+    /// 用 `files/` 中的新文件替换
+    Replace,
+    /// 删除旧版本遗留的文件
+    Remove,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingEntry {
+    /// 以 `/` 分隔的相对路径
+    pub path: String,
+    pub action: PendingAction,
+}
+
+/// 延迟替换列表，保存在 `.update-pending/pending.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingReplacements {
+    #[serde(default)]
+    pub entries: Vec<PendingEntry>,
+}
+
+impl PendingReplacements {
+    /// 读取程序目录中的延迟替换列表，不存在时返回空列表
+    pub fn load(install_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = list_path(install_dir);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("读取延迟替换列表失败: {} ({})", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("解析延迟替换列表失败: {} ({})", path.display(), e).into())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// 延迟替换列表文件路径
+pub fn list_path(install_dir: &Path) -> PathBuf {
+    install_dir.join(PENDING_DIR_NAME).join(PENDING_LIST_NAME)
+}
+
+/// 等待替换的新文件在延迟替换目录中的存放位置
+pub fn staged_file(install_dir: &Path, relative: &str) -> PathBuf {
+    install_dir
+        .join(PENDING_DIR_NAME)
+        .join(PENDING_FILES_DIR)
+        .join(relative)
+}
+
+/// 应用延迟替换的结果
+#[derive(Debug, Default)]
+pub struct PendingOutcome {
+    pub applied: Vec<String>,
+    pub remaining: Vec<String>,
+}
+
+/// 执行上次更新遗留的延迟替换：先将旧文件重命名到一旁，再移入新文件。
+///
+/// 仍被占用的条目继续保留，全部完成后删除延迟替换目录。
+pub fn apply_pending(
+    install_dir: &Path,
+    policy: RetryPolicy,
+    verbose: bool,
+) -> Result<PendingOutcome, Box<dyn Error>> {
+    let mut outcome = PendingOutcome::default();
+    let pending = PendingReplacements::load(install_dir)?;
+    if pending.is_empty() {
+        return Ok(outcome);
+    }
+
+    let pending_dir = install_dir.join(PENDING_DIR_NAME);
+    let mut remaining = Vec::new();
+
+    for entry in pending.entries {
+        let target = install_dir.join(&entry.path);
+        let result = match entry.action {
+            PendingAction::Replace => replace_file(
+                &staged_file(install_dir, &entry.path),
+                &target,
+                &pending_dir.join(OLD_DIR_NAME).join(&entry.path),
+                policy,
+            ),
+            PendingAction::Remove => retry_io(policy, || match fs::remove_file(&target) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            }),
+        };
+
+        match result {
+            Ok(()) => {
+                if verbose {
+                    println!("📝 已完成延迟替换: {}", entry.path);
+                }
+                outcome.applied.push(entry.path);
+            }
+            Err(e) => {
+                if verbose {
+                    println!("⏸️  文件仍被占用: {} ({})", entry.path, e);
+                }
+                outcome.remaining.push(entry.path.clone());
+                remaining.push(entry);
+            }
+        }
+    }
+
+    if remaining.is_empty() {
+        if let Err(e) = fs::remove_dir_all(&pending_dir) {
+            println!(
+                "⚠️  清理延迟替换目录失败: {} ({})",
+                pending_dir.display(),
+                e
+            );
+        }
+    } else {
+        PendingReplacements { entries: remaining }.write(&list_path(install_dir))?;
+    }
+
+    Ok(outcome)
+}
+
+/// 将旧文件移到一旁后再移入新文件；移入失败时把旧文件放回原处
+fn replace_file(source: &Path, target: &Path, aside: &Path, policy: RetryPolicy) -> io::Result<()> {
+    let has_target = target.exists();
+    if has_target {
+        if let Some(parent) = aside.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        retry_io(policy, || fs::rename(target, aside))?;
+    } else if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if let Err(e) = retry_io(policy, || fs::rename(source, target)) {
+        if has_target {
+            let _ = fs::rename(aside, target);
+        }
+        return Err(e);
+    }
+
+    if has_target {
+        let _ = fs::remove_file(aside);
+    }
+    Ok(())
+}
@@ -0,0 +1,340 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::manifest::PackageManifest;
+use super::version::Version;
+
+/// 程序目录中预装插件所在的目录名称
+pub const PLUGINS_DIR_NAME: &str = "Plugins";
+
+/// 插件元数据文件名称，与 `Constant.PluginMetaFileName` 保持一致
+pub const PLUGIN_META_FILE_NAME: &str = "plugin.json";
+
+/// 标记插件目录待删除的文件，主程序下次启动时删除带有该文件的插件目录，与 `Constant.NeedDelete` 保持一致
+pub const NEED_DELETE_FILE_NAME: &str = "NeedDelete.txt";
+
+/// 标记插件目录停用的文件，主程序启动时跳过带有该文件的插件但保留目录，与 `Constant.NeedDisable` 保持一致
+pub const NEED_DISABLE_FILE_NAME: &str = "NeedDisable.txt";
+
+/// `plugin.json` 中更新程序关心的字段
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PluginMeta {
+    #[serde(rename = "PluginID")]
+    pub plugin_id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    /// 插件编译时使用的插件接口版本，与 `PluginMetaData.ApiVersion` 对应，空字符串视为未声明
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub api_version: Option<String>,
+}
+
+/// 更新包声明的插件兼容性要求，位于更新清单的 `pluginCompatibility` 中
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginCompatibility {
+    /// 新版本支持的最低插件接口版本，与 `plugin.json` 中的 `ApiVersion` 比较
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_api_version: Option<String>,
+    /// 新版本支持的最高插件接口版本
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_api_version: Option<String>,
+    /// 针对单个插件的版本要求
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plugins: Vec<PluginRequirement>,
+}
+
+/// 单个插件的版本要求，与 `plugin.json` 中的 `Version` 比较
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginRequirement {
+    pub plugin_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_version: Option<String>,
+}
+
+/// 兼容性检查发现问题的用户插件
+#[derive(Debug)]
+pub struct IncompatiblePlugin {
+    pub dir: PathBuf,
+    pub name: String,
+    pub version: String,
+    pub reason: String,
+}
+
+/// 用户插件的兼容性检查结果
+#[derive(Debug, Default)]
+pub struct CompatibilityCheck {
+    /// 与新版本不兼容的插件
+    pub incompatible: Vec<IncompatiblePlugin>,
+    /// 未声明接口版本、无法判断是否兼容的插件
+    pub unknown: Vec<IncompatiblePlugin>,
+}
+
+/// 插件目录及其元数据
+#[derive(Debug)]
+pub struct PluginFolder {
+    /// 目录名称
+    pub folder: String,
+    pub meta: PluginMeta,
+}
+
+/// 预装插件的更新计划，路径均相对于程序目录
+#[derive(Debug, Default)]
+pub struct PluginPlan {
+    /// 新版本中仍包含的预装插件，原目录整体移除后安装新目录
+    pub replaced: Vec<String>,
+    /// 新版本已不再包含的预装插件，整体移除
+    pub removed: Vec<String>,
+    /// 非预装插件，保持不变
+    pub kept: Vec<String>,
+}
+
+/// 读取插件目录中的 `plugin.json`，文件不存在时返回 `None`
+pub fn read_plugin_meta(dir: &Path) -> Result<Option<PluginMeta>, Box<dyn Error>> {
+    let path = dir.join(PLUGIN_META_FILE_NAME);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("读取插件信息失败: {} ({})", path.display(), e).into()),
+    };
+    let meta = serde_json::from_str(content.trim_start_matches('\u{feff}'))
+        .map_err(|e| format!("解析插件信息失败: {} ({})", path.display(), e))?;
+    Ok(Some(meta))
+}
+
+/// 列出目录下所有带有 `plugin.json` 的插件目录，没有元数据的目录不视为插件
+pub fn scan_plugins(dir: &Path) -> Result<Vec<PluginFolder>, Box<dyn Error>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("读取插件目录失败: {} ({})", dir.display(), e).into()),
+    };
+
+    let mut plugins = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if let Some(meta) = read_plugin_meta(&entry.path())? {
+            plugins.push(PluginFolder {
+                folder: entry.file_name().to_string_lossy().into_owned(),
+                meta,
+            });
+        }
+    }
+    plugins.sort_by(|a, b| a.folder.cmp(&b.folder));
+    Ok(plugins)
+}
+
+/// 以 `PluginID` 为单位对比程序目录与暂存目录中的预装插件。
+///
+/// 已安装插件的 ID 出现在新版本中时整体替换（即使目录名已改变）；
+/// 属于预装插件（由上一版本安装，或 ID 在更新清单的 `prePluginIds` 中）但新版本不再包含时整体移除；
+/// 其余插件视为用户自行安装，保持不变。
+pub fn plan_plugins(
+    install_dir: &Path,
+    staging_dir: &Path,
+    previous: Option<&PackageManifest>,
+    pre_plugin_ids: &[String],
+) -> Result<PluginPlan, Box<dyn Error>> {
+    let incoming: BTreeSet<String> = scan_plugins(&staging_dir.join(PLUGINS_DIR_NAME))?
+        .into_iter()
+        .map(|plugin| plugin.meta.plugin_id.to_lowercase())
+        .collect();
+    let shipped = previous_plugin_folders(previous);
+
+    let mut plan = PluginPlan::default();
+    for plugin in scan_plugins(&install_dir.join(PLUGINS_DIR_NAME))? {
+        let id = plugin.meta.plugin_id.to_lowercase();
+        let relative = format!("{}/{}", PLUGINS_DIR_NAME, plugin.folder);
+        if incoming.contains(&id) {
+            plan.replaced.push(relative);
+        } else if pre_plugin_ids
+            .iter()
+            .any(|pre| pre.eq_ignore_ascii_case(&id))
+            || shipped.contains(&plugin.folder.to_lowercase())
+        {
+            plan.removed.push(relative);
+        } else {
+            plan.kept.push(relative);
+        }
+    }
+    Ok(plan)
+}
+
+/// 上一版本安装记录中 `Plugins` 下的目录名称（小写）
+fn previous_plugin_folders(previous: Option<&PackageManifest>) -> BTreeSet<String> {
+    let prefix = format!("{}/", PLUGINS_DIR_NAME.to_lowercase());
+    previous
+        .into_iter()
+        .flat_map(|manifest| &manifest.files)
+        .filter_map(|file| {
+            let lower = file.path.to_lowercase();
+            let rest = lower.strip_prefix(&prefix)?;
+            let (folder, _) = rest.split_once('/')?;
+            Some(folder.to_string())
+        })
+        .collect()
+}
+
+/// 按更新包声明的兼容性要求检查用户插件目录中的插件，已标记待删除或已停用的插件不再检查
+pub fn check_compatibility(
+    plugins_dir: &Path,
+    compatibility: &PluginCompatibility,
+) -> Result<CompatibilityCheck, Box<dyn Error>> {
+    let min_api = parse_bound(compatibility.min_api_version.as_deref())?;
+    let max_api = parse_bound(compatibility.max_api_version.as_deref())?;
+
+    let mut check = CompatibilityCheck::default();
+    for plugin in scan_plugins(plugins_dir)? {
+        let dir = plugins_dir.join(&plugin.folder);
+        if dir.join(NEED_DELETE_FILE_NAME).is_file() || dir.join(NEED_DISABLE_FILE_NAME).is_file() {
+            continue;
+        }
+
+        let meta = &plugin.meta;
+        let mut reasons = Vec::new();
+        let mut unknown = false;
+        if min_api.is_some() || max_api.is_some() {
+            match meta.api_version.as_deref().map(Version::parse) {
+                Some(Some(api)) => {
+                    reasons.extend(out_of_range(&api, &min_api, &max_api, "接口版本"))
+                }
+                Some(None) => reasons.push("接口版本无效".to_string()),
+                None => unknown = true,
+            }
+        }
+
+        for requirement in &compatibility.plugins {
+            if !requirement.plugin_id.eq_ignore_ascii_case(&meta.plugin_id) {
+                continue;
+            }
+            let min = parse_bound(requirement.min_version.as_deref())?;
+            let max = parse_bound(requirement.max_version.as_deref())?;
+            match Version::parse(&meta.version) {
+                Some(version) => reasons.extend(out_of_range(&version, &min, &max, "插件版本")),
+                None => reasons.push(format!("插件版本无效: {}", meta.version)),
+            }
+        }
+
+        let plugin = |reason: String| IncompatiblePlugin {
+            dir,
+            name: meta.name.clone(),
+            version: meta.version.clone(),
+            reason,
+        };
+        if !reasons.is_empty() {
+            check.incompatible.push(plugin(reasons.join("，")));
+        } else if unknown {
+            // 版本要求均已满足，只是接口版本无法判断
+            check.unknown.push(plugin("未声明接口版本".to_string()));
+        }
+    }
+    Ok(check)
+}
+
+/// 在插件目录中创建停用标记并写入原因，主程序启动时跳过该插件；删除标记文件即可重新启用
+pub fn mark_disabled(dir: &Path, reason: &str) -> io::Result<()> {
+    fs::write(dir.join(NEED_DISABLE_FILE_NAME), reason)
+}
+
+fn empty_as_none<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let text: Option<String> = Option::deserialize(deserializer)?;
+    Ok(text.filter(|text| !text.trim().is_empty()))
+}
+
+fn parse_bound(text: Option<&str>) -> Result<Option<Version>, Box<dyn Error>> {
+    text.map(|text| {
+        Version::parse(text).ok_or_else(|| format!("插件兼容性要求中的版本号无效: {}", text).into())
+    })
+    .transpose()
+}
+
+fn out_of_range(
+    version: &Version,
+    min: &Option<Version>,
+    max: &Option<Version>,
+    label: &str,
+) -> Option<String> {
+    if let Some(min) = min
+        && version < min
+    {
+        return Some(format!("{} {} 低于 {}", label, version, min));
+    }
+    if let Some(max) = max
+        && version > max
+    {
+        return Some(format!("{} {} 高于 {}", label, version, max));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(root: &Path, folder: &str, api_version: Option<&str>) -> PathBuf {
+        let dir = root.join(folder);
+        fs::create_dir_all(&dir).unwrap();
+        let api = api_version
+            .map(|api| format!(r#","ApiVersion":"{}""#, api))
+            .unwrap_or_default();
+        let meta = format!(
+            r#"{{"PluginID":"{}","Name":"{}","Version":"1.0.0"{}}}"#,
+            folder, folder, api
+        );
+        fs::write(dir.join(PLUGIN_META_FILE_NAME), meta).unwrap();
+        dir
+    }
+
+    fn requirement() -> PluginCompatibility {
+        PluginCompatibility {
+            min_api_version: Some("2.0.0".to_string()),
+            max_api_version: None,
+            plugins: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn missing_api_version_is_reported_as_unknown() {
+        let temp = tempfile::tempdir().unwrap();
+        plugin(temp.path(), "old", Some("1.0.0"));
+        plugin(temp.path(), "current", Some("2.1.0"));
+        plugin(temp.path(), "undeclared", None);
+
+        let check = check_compatibility(temp.path(), &requirement()).unwrap();
+        let names = |plugins: &[IncompatiblePlugin]| {
+            plugins.iter().map(|p| p.name.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(names(&check.incompatible), ["old"]);
+        assert_eq!(names(&check.unknown), ["undeclared"]);
+    }
+
+    #[test]
+    fn disabled_plugins_keep_their_files_and_are_not_rechecked() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = plugin(temp.path(), "old", Some("1.0.0"));
+
+        mark_disabled(&dir, "接口版本 1.0.0 低于 2.0.0").unwrap();
+
+        // 停用不能使用主程序会删除目录的 NeedDelete.txt
+        assert!(dir.join(NEED_DISABLE_FILE_NAME).is_file());
+        assert!(!dir.join(NEED_DELETE_FILE_NAME).exists());
+        assert!(dir.join(PLUGIN_META_FILE_NAME).is_file());
+        let check = check_compatibility(temp.path(), &requirement()).unwrap();
+        assert!(check.incompatible.is_empty());
+        assert!(check.unknown.is_empty());
+    }
+}
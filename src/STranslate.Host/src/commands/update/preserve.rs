@@ -0,0 +1,146 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::download::CACHE_DIR_NAME;
+use super::manifest::to_manifest_path;
+use super::pending::PENDING_DIR_NAME;
+use super::self_update::{HOST_NEW_SUFFIX, HOST_OLD_SUFFIX};
+use super::transaction::WORK_DIR_NAME;
+
+/// 默认保留的目录，与 `Constant.cs` 中的 `Logs`、`PortableConfig` 以及缓存压缩包所在的 `tmp` 对应
+pub const DEFAULT_PRESERVE_PATTERNS: [&str; 3] = ["Logs", "PortableConfig", "tmp"];
+
+/// 宿主程序文件名，与 `Constant.HostExeName` 保持一致
+pub const HOST_EXE_NAME: &str = "z_stranslate_host.exe";
+
+/// 便携模式数据目录名称，与 `Constant.PortableFolderName` 保持一致
+pub const PORTABLE_FOLDER_NAME: &str = "PortableConfig";
+
+/// 主程序未使用便携配置时的数据目录名称（位于 `%APPDATA%` 下）
+const APP_NAME: &str = "STranslate";
+
+/// 主程序当前使用的数据目录，与 `DataLocation.DataDirectory()` 一致：
+/// 存在便携配置目录时使用该目录，否则使用 `%APPDATA%\STranslate`
+pub fn default_data_dir(install_dir: &Path) -> Option<PathBuf> {
+    let portable = install_dir.join(PORTABLE_FOLDER_NAME);
+    if portable.is_dir() {
+        Some(portable)
+    } else {
+        Some(PathBuf::from(std::env::var_os("APPDATA")?).join(APP_NAME))
+    }
+}
+
+/// `--clean` 时的保留规则。
+///
+/// 模式相对于程序目录匹配，大小写不敏感，`*` 不跨越 `/`，`**` 可匹配任意层级。
+/// 宿主程序自身、当前数据目录、更新工作目录与延迟替换目录始终保留。
+pub struct PreserveRules {
+    patterns: Vec<String>,
+    globs: GlobSet,
+    protected: Vec<String>,
+}
+
+impl PreserveRules {
+    pub fn new(
+        install_dir: &Path,
+        patterns: &[String],
+        data_dir: Option<&Path>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = GlobBuilder::new(pattern.trim_matches('/'))
+                .case_insensitive(true)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| format!("无效的保留规则 '{}': {}", pattern, e))?;
+            builder.add(glob);
+        }
+
+        let mut protected = vec![
+            HOST_EXE_NAME.to_string(),
+            format!("{}{}", HOST_EXE_NAME, HOST_NEW_SUFFIX),
+            format!("{}{}", HOST_EXE_NAME, HOST_OLD_SUFFIX),
+            WORK_DIR_NAME.to_string(),
+            PENDING_DIR_NAME.to_string(),
+            CACHE_DIR_NAME.to_string(),
+        ];
+
+        if let Ok(current_exe) = std::env::current_exe()
+            && let Some(relative) = relative_to(install_dir, &current_exe)
+        {
+            protected.push(relative);
+        }
+
+        let portable = install_dir.join(PORTABLE_FOLDER_NAME);
+        let data_dir = data_dir.map(Path::to_path_buf).or_else(|| {
+            if portable.is_dir() {
+                Some(portable)
+            } else {
+                None
+            }
+        });
+        if let Some(relative) = data_dir.and_then(|dir| relative_to(install_dir, &dir)) {
+            protected.push(relative);
+        }
+
+        Ok(Self {
+            patterns: patterns.to_vec(),
+            globs: builder.build()?,
+            protected: protected.into_iter().map(|p| p.to_lowercase()).collect(),
+        })
+    }
+
+    /// 判断相对路径（或其任一上级目录）是否应被保留
+    pub fn is_preserved(&self, relative: &Path) -> bool {
+        let path = to_manifest_path(relative);
+        let lower = path.to_lowercase();
+
+        if self
+            .protected
+            .iter()
+            .any(|p| lower == *p || lower.starts_with(&format!("{}/", p)))
+        {
+            return true;
+        }
+
+        let mut current = PathBuf::new();
+        relative.components().any(|component| {
+            current.push(component);
+            self.globs.is_match(to_manifest_path(&current))
+        })
+    }
+
+    /// 判断目录中是否存在需要保留的条目，存在时需要逐项清理而不能整体移除
+    pub fn contains_preserved(&self, install_dir: &Path, relative: &Path) -> io::Result<bool> {
+        for entry in fs::read_dir(install_dir.join(relative))? {
+            let entry = entry?;
+            let child = relative.join(entry.file_name());
+            if self.is_preserved(&child)
+                || (entry.file_type()?.is_dir() && self.contains_preserved(install_dir, &child)?)
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn describe(&self) -> String {
+        self.patterns.join(", ")
+    }
+}
+
+/// 若 `path` 位于 `base` 之下，返回以 `/` 分隔的相对路径
+fn relative_to(base: &Path, path: &Path) -> Option<String> {
+    let base = base.canonicalize().ok()?;
+    let path = path.canonicalize().ok()?;
+    let relative = path.strip_prefix(&base).ok()?;
+    let relative = to_manifest_path(relative);
+    if relative.is_empty() {
+        None
+    } else {
+        Some(relative)
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::download::CACHE_DIR_NAME;
+use super::manifest::{
+    INSTALLED_MANIFEST_NAME, PackageManifest, list_files, sha256_file, to_manifest_path,
+};
+use super::pending::PENDING_DIR_NAME;
+use super::preserve::{DEFAULT_PRESERVE_PATTERNS, HOST_EXE_NAME};
+use super::self_update::{HOST_NEW_SUFFIX, HOST_OLD_SUFFIX};
+use super::transaction::WORK_DIR_NAME;
+
+/// 对比新旧版本清单得到的清理计划
+#[derive(Debug, Default)]
+pub struct PrunePlan {
+    /// 旧版本安装、新版本不再包含且未被修改的文件，需要删除
+    pub stale: Vec<String>,
+    /// 旧版本安装、新版本不再包含但内容已被修改的文件，保留
+    pub modified: Vec<String>,
+    /// 不属于新旧任一版本的文件，保留
+    pub unknown: Vec<String>,
+}
+
+/// 根据上一版本的安装记录与新版本的文件列表生成清理计划
+pub fn plan_prune(
+    install_dir: &Path,
+    previous: &PackageManifest,
+    new_files: &BTreeSet<String>,
+) -> io::Result<PrunePlan> {
+    let mut plan = PrunePlan::default();
+    let mut previous_files = BTreeSet::new();
+
+    for file in &previous.files {
+        previous_files.insert(file.path.as_str());
+        if new_files.contains(&file.path) {
+            continue;
+        }
+
+        let full_path = install_dir.join(&file.path);
+        if !full_path.is_file() {
+            continue;
+        }
+
+        let unchanged = fs::metadata(&full_path)?.len() == file.size
+            && sha256_file(&full_path)?.eq_ignore_ascii_case(&file.sha256);
+        if unchanged {
+            plan.stale.push(file.path.clone());
+        } else {
+            plan.modified.push(file.path.clone());
+        }
+    }
+
+    for path in list_files(install_dir)? {
+        if is_internal(&path) || previous_files.contains(path.as_str()) || new_files.contains(&path)
+        {
+            continue;
+        }
+        plan.unknown.push(path);
+    }
+
+    Ok(plan)
+}
+
+/// 被删除文件的所有上级目录（由深到浅），不包括程序目录本身
+pub fn parent_dirs(removed: &[String]) -> Vec<String> {
+    let mut parents = BTreeSet::new();
+    for path in removed {
+        let mut current = Path::new(path).parent();
+        while let Some(dir) = current {
+            if dir.as_os_str().is_empty() {
+                break;
+            }
+            parents.insert(to_manifest_path(dir));
+            current = dir.parent();
+        }
+    }
+
+    let mut parents: Vec<String> = parents.into_iter().collect();
+    parents.sort_by_key(|p| std::cmp::Reverse(p.matches('/').count()));
+    parents
+}
+
+pub fn is_empty_dir(path: &Path) -> bool {
+    fs::read_dir(path)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(false)
+}
+
+/// 数据目录、更新工作目录、延迟替换目录、下载缓存、安装记录与宿主程序（含暂存和替换下来的副本）不参与版本对比
+fn is_internal(path: &str) -> bool {
+    let top = path.split('/').next().unwrap_or("");
+    top == WORK_DIR_NAME
+        || top == PENDING_DIR_NAME
+        || top == CACHE_DIR_NAME
+        || top == INSTALLED_MANIFEST_NAME
+        || top.eq_ignore_ascii_case(HOST_EXE_NAME)
+        || [HOST_NEW_SUFFIX, HOST_OLD_SUFFIX]
+            .iter()
+            .any(|suffix| top.eq_ignore_ascii_case(&format!("{}{}", HOST_EXE_NAME, suffix)))
+        || DEFAULT_PRESERVE_PATTERNS
+            .iter()
+            .any(|name| top.eq_ignore_ascii_case(name))
+}
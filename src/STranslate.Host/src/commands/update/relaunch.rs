@@ -0,0 +1,80 @@
+use clap::ValueEnum;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::commands::start::start_task_scheduler;
+use crate::process::{is_elevated, spawn_unelevated};
+
+/// 跳过 UAC 的任务计划名称，与 `Constant.TaskName` 保持一致
+pub const SKIP_UAC_TASK_NAME: &str = "STranslateSkipUAC";
+
+/// 更新完成后启动程序使用的权限
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LaunchPrivilege {
+    /// 与更新程序相同
+    Same,
+    /// 以普通用户权限启动（更新程序已提升时降权）
+    Unelevated,
+    /// 通过跳过 UAC 的任务计划以管理员身份启动
+    Task,
+}
+
+/// 更新完成后重新启动程序的方式
+pub struct RelaunchOptions {
+    /// 可执行文件，相对路径相对于程序目录
+    pub exe: PathBuf,
+    pub args: Vec<String>,
+    /// 工作目录，默认为程序目录
+    pub working_dir: Option<PathBuf>,
+    pub privilege: LaunchPrivilege,
+    /// `Task` 方式使用的任务计划名称
+    pub task_name: String,
+}
+
+/// 启动更新后的程序
+pub fn relaunch(
+    install_dir: &Path,
+    options: &RelaunchOptions,
+    verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    if options.privilege == LaunchPrivilege::Task {
+        // 任务计划中已记录程序路径与参数
+        start_task_scheduler(&options.task_name, verbose)?;
+        println!("✅ 程序已通过任务计划启动");
+        return Ok(());
+    }
+
+    let exe_path = install_dir.join(&options.exe);
+    if !exe_path.is_file() {
+        println!("⚠️  {} 不存在，跳过自动启动", exe_path.display());
+        return Ok(());
+    }
+    let working_dir = options
+        .working_dir
+        .as_deref()
+        .map_or_else(|| install_dir.to_path_buf(), |dir| install_dir.join(dir));
+
+    if verbose {
+        println!("🚀 启动 {}...", exe_path.display());
+        if !options.args.is_empty() {
+            println!("   参数: {:?}", options.args);
+        }
+        println!("   工作目录: {}", working_dir.display());
+    }
+
+    if options.privilege == LaunchPrivilege::Unelevated && is_elevated() {
+        if verbose {
+            println!("🔓 更新程序以管理员身份运行，降权启动");
+        }
+        spawn_unelevated(&exe_path, &options.args, &working_dir)
+            .map_err(|e| format!("以普通用户权限启动失败: {}", e))?;
+    } else {
+        Command::new(&exe_path)
+            .args(&options.args)
+            .current_dir(&working_dir)
+            .spawn()?;
+    }
+    println!("✅ 程序已启动");
+    Ok(())
+}
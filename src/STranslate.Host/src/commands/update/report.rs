@@ -0,0 +1,125 @@
+use super::migration::MigrationPlan;
+use super::plugins::IncompatiblePlugin;
+use super::self_update::HostUpdate;
+
+/// 更新结果报告，在事务提交后输出
+#[derive(Debug, Default)]
+pub struct UpdateReport {
+    /// 更新前已安装的版本，无法确定时为 `None`
+    pub installed_version: Option<String>,
+    /// 更新包的版本，无法确定时为 `None`
+    pub package_version: Option<String>,
+    /// 本次安装的全部更新包（主更新包在前）
+    pub packages: Vec<String>,
+    /// 写入程序目录的文件数（含暂存的宿主程序）
+    pub written: usize,
+    /// 与现有文件内容相同而跳过的文件数
+    pub unchanged: usize,
+    /// 属于旧版本且新版本中已不存在、被删除的文件
+    pub removed: Vec<String>,
+    /// 属于旧版本但已被修改、因此保留的文件
+    pub kept_modified: Vec<String>,
+    /// 不属于任何已知版本的文件（用户添加的文件、用户安装的插件等），保持不变
+    pub unknown: Vec<String>,
+    /// 整体替换的预装插件目录
+    pub plugins_replaced: Vec<String>,
+    /// 新版本已不再包含、被移除的预装插件目录
+    pub plugins_removed: Vec<String>,
+    /// 非预装插件目录，保持不变
+    pub plugins_kept: Vec<String>,
+    /// 与新版本不兼容的用户插件
+    pub incompatible_plugins: Vec<IncompatiblePlugin>,
+    /// 未声明接口版本、无法判断是否兼容的用户插件
+    pub unknown_plugins: Vec<IncompatiblePlugin>,
+    /// 更新包声明了设置迁移时的执行结果，提交后写入设置文件
+    pub settings_migration: Option<MigrationPlan>,
+    /// 因被占用而延迟到下次启动时替换或删除的文件
+    pub deferred: Vec<String>,
+    /// 更新包包含正在运行的宿主程序时，新宿主程序已暂存，提交后再替换
+    pub host_staged: bool,
+    /// 宿主程序的替换结果
+    pub host_update: Option<HostUpdate>,
+}
+
+impl UpdateReport {
+    pub fn print(&self) {
+        println!("📋 更新报告:");
+        self.print_versions();
+        println!(
+            "   写入文件: {}，内容未变而跳过: {}",
+            self.written, self.unchanged
+        );
+        print_list("删除旧版本文件", &self.removed);
+        print_list("保留已修改的旧版本文件", &self.kept_modified);
+        print_list("未知文件（未改动）", &self.unknown);
+        print_list("替换预装插件", &self.plugins_replaced);
+        print_list("移除预装插件", &self.plugins_removed);
+        print_list("保留非预装插件", &self.plugins_kept);
+        self.print_checks();
+        print_list("延迟到下次启动时处理", &self.deferred);
+        match &self.host_update {
+            Some(HostUpdate::Replaced(version)) => println!(
+                "   宿主程序: 已更新，当前版本 {}",
+                version.as_deref().unwrap_or("未知")
+            ),
+            Some(HostUpdate::Pending) => println!("   宿主程序: 将在下次运行时替换"),
+            Some(HostUpdate::Failed(reason)) => {
+                println!("   宿主程序: 更新失败，继续使用原有版本 ({})", reason)
+            }
+            None => {}
+        }
+    }
+
+    /// 试运行只经过校验阶段，只输出版本、插件兼容性与设置迁移
+    pub fn print_dry_run(&self) {
+        println!("📋 试运行报告（未修改任何文件）:");
+        self.print_versions();
+        self.print_checks();
+        if let Some(migration) = &self.settings_migration {
+            let files: Vec<String> = migration
+                .changed_files()
+                .into_iter()
+                .map(String::from)
+                .collect();
+            print_list("将修改的设置文件", &files);
+        }
+    }
+
+    fn print_versions(&self) {
+        println!(
+            "   版本: {} → {}",
+            self.installed_version.as_deref().unwrap_or("未知"),
+            self.package_version.as_deref().unwrap_or("未知")
+        );
+        // 只有一个更新包时版本行已足够
+        if self.packages.len() > 1 {
+            print_list("更新包", &self.packages);
+        }
+    }
+
+    fn print_checks(&self) {
+        let incompatible: Vec<String> = self
+            .incompatible_plugins
+            .iter()
+            .map(|plugin| format!("{} ({})", plugin.dir.display(), plugin.reason))
+            .collect();
+        print_list("不兼容的用户插件", &incompatible);
+        let unknown: Vec<String> = self
+            .unknown_plugins
+            .iter()
+            .map(|plugin| format!("{} ({})", plugin.dir.display(), plugin.reason))
+            .collect();
+        print_list("兼容性未知的用户插件", &unknown);
+        if let Some(migration) = &self.settings_migration {
+            print_list("执行的设置迁移", &migration.applied);
+            print_list("跳过的设置迁移", &migration.skipped);
+        }
+    }
+}
+
+fn print_list(title: &str, items: &[String]) {
+    println!("   {}: {}", title, items.len());
+    for item in items {
+        println!("     - {}", item);
+    }
+}
@@ -0,0 +1,69 @@
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+/// 文件被占用时的重试策略（指数退避）
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// 最多尝试次数（包括第一次）
+    pub attempts: u32,
+    /// 第一次重试前的等待时间，之后每次翻倍
+    pub initial_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// 只尝试一次，用于启动时应用延迟替换等不宜阻塞的场景
+    pub const ONCE: RetryPolicy = RetryPolicy {
+        attempts: 1,
+        initial_delay: Duration::ZERO,
+    };
+}
+
+impl Default for RetryPolicy {
+    /// 共 6 次尝试，累计等待约 3 秒
+    fn default() -> Self {
+        Self {
+            attempts: 6,
+            initial_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+/// 执行文件操作，遇到占用或权限错误时按退避策略重试，返回最后一次的原始错误
+pub fn retry_io<T>(
+    policy: RetryPolicy,
+    mut operation: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut delay = policy.initial_delay;
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Err(e) if attempt < policy.attempts && is_locked(&e) => {
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// 判断错误是否由文件被占用引起（杀毒软件、资源管理器或仍在运行的程序持有句柄）
+pub fn is_locked(error: &io::Error) -> bool {
+    #[cfg(windows)]
+    {
+        use winapi::shared::winerror::{ERROR_LOCK_VIOLATION, ERROR_SHARING_VIOLATION};
+        if let Some(code) = error.raw_os_error()
+            && (code as u32 == ERROR_SHARING_VIOLATION || code as u32 == ERROR_LOCK_VIOLATION)
+        {
+            return true;
+        }
+    }
+
+    matches!(
+        error.kind(),
+        io::ErrorKind::PermissionDenied
+            | io::ErrorKind::ResourceBusy
+            | io::ErrorKind::ExecutableFileBusy
+    )
+}
@@ -0,0 +1,151 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::manifest::{PackageManifest, sha256_file};
+use super::preserve::HOST_EXE_NAME;
+
+/// 暂存的新宿主程序后缀，更新提交后再替换正在运行的宿主程序
+pub const HOST_NEW_SUFFIX: &str = ".new";
+
+/// 被替换下来的旧宿主程序后缀，正在运行的程序无法删除，下次运行时再删除
+pub const HOST_OLD_SUFFIX: &str = ".old";
+
+/// 宿主程序自我更新的结果
+#[derive(Debug)]
+pub enum HostUpdate {
+    /// 已替换并通过校验，附带新宿主程序报告的版本
+    Replaced(Option<String>),
+    /// 暂时无法替换，新宿主程序保留在暂存位置，下次运行时再替换
+    Pending,
+    /// 替换失败，继续使用原有宿主程序，附带失败原因
+    Failed(String),
+}
+
+/// 判断当前进程是否正是程序目录中的宿主程序
+pub fn is_running_host(install_dir: &Path) -> bool {
+    let Ok(current) = std::env::current_exe().and_then(fs::canonicalize) else {
+        return false;
+    };
+    fs::canonicalize(install_dir.join(HOST_EXE_NAME)).is_ok_and(|host| host == current)
+}
+
+/// 新宿主程序的暂存位置
+pub fn staged_host(install_dir: &Path) -> PathBuf {
+    with_suffix(&install_dir.join(HOST_EXE_NAME), HOST_NEW_SUFFIX)
+}
+
+/// 按安装记录校验暂存的新宿主程序后，用它替换程序目录中的宿主程序，并读取替换后的版本。
+///
+/// 正在运行的可执行文件不能覆盖或删除，但可以重命名：先将其改名为 `.old`，
+/// 再将 `.new` 移到原位置，当前进程继续从改名后的文件运行。
+pub fn finish_host_update(install_dir: &Path, verbose: bool) -> Result<HostUpdate, Box<dyn Error>> {
+    let host = install_dir.join(HOST_EXE_NAME);
+    let staged = staged_host(install_dir);
+    let old = with_suffix(&host, HOST_OLD_SUFFIX);
+    remove_old_host(&old);
+
+    if let Err(e) = verify_host(install_dir, &staged) {
+        // 损坏的暂存文件不能留到下次运行时再替换
+        let _ = fs::remove_file(&staged);
+        return Err(e);
+    }
+
+    if host.exists()
+        && let Err(e) = fs::rename(&host, &old)
+    {
+        if verbose {
+            println!("⚠️  无法移走正在使用的宿主程序: {} ({})", host.display(), e);
+        }
+        return Ok(HostUpdate::Pending);
+    }
+    if let Err(e) = fs::rename(&staged, &host) {
+        // 放回旧宿主程序，保证程序目录中始终有可用的宿主程序
+        let _ = fs::rename(&old, &host);
+        if verbose {
+            println!("⚠️  无法放置新的宿主程序: {} ({})", host.display(), e);
+        }
+        return Ok(HostUpdate::Pending);
+    }
+    if verbose {
+        println!("🔁 已替换宿主程序: {}", host.display());
+    }
+    Ok(HostUpdate::Replaced(host_version(&host)))
+}
+
+/// 每次运行时完成上次未能完成的宿主程序替换，并删除替换下来的旧宿主程序
+pub fn finish_host_update_on_launch() {
+    let Some(install_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    else {
+        return;
+    };
+
+    if !staged_host(&install_dir).is_file() {
+        remove_old_host(&with_suffix(
+            &install_dir.join(HOST_EXE_NAME),
+            HOST_OLD_SUFFIX,
+        ));
+        return;
+    }
+    // 输出到标准错误，避免混入 check-update 等命令供脚本解析的标准输出
+    match finish_host_update(&install_dir, false) {
+        Ok(HostUpdate::Replaced(version)) => eprintln!(
+            "✅ 已完成宿主程序更新，当前版本: {}",
+            version.as_deref().unwrap_or("未知")
+        ),
+        Ok(HostUpdate::Pending | HostUpdate::Failed(_)) => {}
+        Err(e) => eprintln!("⚠️  完成宿主程序更新失败: {}", e),
+    }
+}
+
+/// 新宿主程序必须与安装记录一致，无法确认时拒绝替换
+fn verify_host(install_dir: &Path, host: &Path) -> Result<(), Box<dyn Error>> {
+    let Some(record) = PackageManifest::load_installed(install_dir)? else {
+        return Err(format!("缺少安装记录，无法校验新的宿主程序: {}", host.display()).into());
+    };
+    let Some(expected) = record
+        .files
+        .iter()
+        .find(|file| file.path.eq_ignore_ascii_case(HOST_EXE_NAME))
+    else {
+        return Err(format!("安装记录中没有宿主程序，无法校验: {}", host.display()).into());
+    };
+
+    let actual = sha256_file(host)?;
+    if !actual.eq_ignore_ascii_case(&expected.sha256) {
+        return Err(format!(
+            "新的宿主程序与安装记录不一致: {} (期望 {}，实际 {})",
+            host.display(),
+            expected.sha256,
+            actual
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// 运行新的宿主程序读取其版本号（`z_stranslate_host <版本>`）
+fn host_version(host: &Path) -> Option<String> {
+    let output = Command::new(host).arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .last()
+        .map(str::to_string)
+}
+
+/// 旧宿主程序仍在运行（例如本次更新正是由它发起）时删除失败，下次运行时再删除
+fn remove_old_host(old: &Path) {
+    let _ = fs::remove_file(old);
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
@@ -0,0 +1,312 @@
+use minisign_verify::{PublicKey, Signature};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// 编译时通过环境变量 `STRANSLATE_UPDATE_PUBLIC_KEY` 内置的 minisign 公钥（base64）
+const EMBEDDED_PUBLIC_KEY: Option<&str> = option_env!("STRANSLATE_UPDATE_PUBLIC_KEY");
+
+/// 默认的签名文件后缀，例如 `STranslate.zip.minisig`
+pub const SIGNATURE_SUFFIX: &str = ".minisig";
+
+/// 签名校验失败，主程序据此返回独立的退出码
+#[derive(Debug)]
+pub struct SignatureError(pub String);
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "签名校验失败: {}", self.0)
+    }
+}
+
+impl Error for SignatureError {}
+
+/// 签名校验选项
+#[derive(Default)]
+pub struct SignatureOptions {
+    /// 主更新包显式指定的签名文件，其他更新包与清单使用 `<文件>.minisig`
+    pub signature: Option<PathBuf>,
+    /// 受信任的公钥文件
+    pub public_key: Option<PathBuf>,
+    /// 必须通过签名校验才允许更新
+    pub required: bool,
+}
+
+/// 签名校验结果
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// 已通过签名校验
+    Verified,
+    /// 未配置签名校验（没有签名文件或公钥），已跳过
+    Skipped,
+}
+
+/// 校验压缩包的 minisign（Ed25519）分离签名。
+///
+/// 未要求签名时，只有在签名文件与公钥都存在的情况下才会校验；
+/// 一旦进行校验，失败即拒绝更新。
+pub fn verify_archive_signature(
+    archive: &Path,
+    signature_path: &Path,
+    options: &SignatureOptions,
+    verbose: bool,
+) -> Result<SignatureStatus, SignatureError> {
+    if archive.is_dir() {
+        // 分离签名针对单个文件，已解压的目录只能依靠清单校验
+        if options.required {
+            return Err(SignatureError(format!(
+                "目录形式的更新包无法校验签名: {}",
+                archive.display()
+            )));
+        }
+        if verbose {
+            println!("ℹ️  更新包为目录，跳过签名校验");
+        }
+        return Ok(SignatureStatus::Skipped);
+    }
+
+    verify_signature(Signed::File(archive), signature_path, options, verbose)
+}
+
+/// 校验外部清单的分离签名（`<清单>.minisig`），校验的是已读入内存、随后用于解析的内容
+pub fn verify_manifest_signature(
+    manifest: &Path,
+    content: &[u8],
+    options: &SignatureOptions,
+    verbose: bool,
+) -> Result<SignatureStatus, SignatureError> {
+    verify_signature(
+        Signed::Bytes(content),
+        &default_signature_path(manifest),
+        options,
+        verbose,
+    )
+    .map_err(|e| SignatureError(format!("清单 {}: {}", manifest.display(), e.0)))
+}
+
+/// 被签名的内容
+enum Signed<'a> {
+    File(&'a Path),
+    Bytes(&'a [u8]),
+}
+
+fn verify_signature(
+    signed: Signed,
+    signature_path: &Path,
+    options: &SignatureOptions,
+    verbose: bool,
+) -> Result<SignatureStatus, SignatureError> {
+    let keys = trusted_keys(options.public_key.as_deref())?;
+
+    if !options.required && (keys.is_empty() || !signature_path.is_file()) {
+        if verbose {
+            println!("ℹ️  未配置签名校验，跳过");
+        }
+        return Ok(SignatureStatus::Skipped);
+    }
+
+    if keys.is_empty() {
+        return Err(SignatureError(
+            "没有可用的受信任公钥（请通过 --public-key 指定或在编译时内置）".to_string(),
+        ));
+    }
+
+    let signature = Signature::from_file(signature_path).map_err(|e| {
+        SignatureError(format!(
+            "无法读取签名文件 {}: {}",
+            signature_path.display(),
+            e
+        ))
+    })?;
+
+    if verbose {
+        println!("🔏 正在校验签名: {}", signature_path.display());
+    }
+
+    let mut last_error = String::from("没有匹配的公钥");
+    for key in &keys {
+        let result = match &signed {
+            Signed::File(path) => verify_with_key(path, key, &signature),
+            Signed::Bytes(content) => key
+                .verify(content, &signature, true)
+                .map_err(|e| e.to_string()),
+        };
+        match result {
+            Ok(()) => {
+                println!("✅ 签名校验通过 ({})", signature.trusted_comment());
+                return Ok(SignatureStatus::Verified);
+            }
+            Err(e) => last_error = e,
+        }
+    }
+
+    Err(SignatureError(last_error))
+}
+
+/// 默认的签名文件位置：`<文件>.minisig`
+pub fn default_signature_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(SIGNATURE_SUFFIX);
+    path.with_file_name(name)
+}
+
+/// 收集受信任的公钥：编译时内置的公钥与 `--public-key` 指定的文件
+fn trusted_keys(key_file: Option<&Path>) -> Result<Vec<PublicKey>, SignatureError> {
+    let mut keys = Vec::new();
+
+    if let Some(encoded) = EMBEDDED_PUBLIC_KEY {
+        let key = PublicKey::from_base64(encoded.trim())
+            .map_err(|e| SignatureError(format!("内置公钥无效: {}", e)))?;
+        keys.push(key);
+    }
+
+    if let Some(path) = key_file {
+        let content = fs::read_to_string(path)
+            .map_err(|e| SignatureError(format!("无法读取公钥文件 {}: {}", path.display(), e)))?;
+        let key = if content.trim_start().starts_with("untrusted comment:") {
+            PublicKey::decode(&content)
+        } else {
+            PublicKey::from_base64(content.trim())
+        }
+        .map_err(|e| SignatureError(format!("公钥文件无效 {}: {}", path.display(), e)))?;
+        keys.push(key);
+    }
+
+    Ok(keys)
+}
+
+fn verify_with_key(archive: &Path, key: &PublicKey, signature: &Signature) -> Result<(), String> {
+    let read_error = |e: std::io::Error| format!("读取压缩包失败: {}", e);
+
+    match key.verify_stream(signature) {
+        Ok(mut verifier) => {
+            let mut file = File::open(archive).map_err(read_error)?;
+            let mut buffer = vec![0u8; 64 * 1024];
+            loop {
+                let read = file.read(&mut buffer).map_err(read_error)?;
+                if read == 0 {
+                    break;
+                }
+                verifier.update(&buffer[..read]);
+            }
+            verifier.finalize().map_err(|e| e.to_string())
+        }
+        Err(minisign_verify::Error::UnsupportedLegacyMode) => {
+            let content = fs::read(archive).map_err(read_error)?;
+            key.verify(&content, signature, true)
+                .map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::update::transaction::{UpdateError, UpdatePhase};
+    use crate::commands::update::{EXIT_CODE_SIGNATURE, update_exit_code};
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/signing")
+            .join(name)
+    }
+
+    /// 把固定测试密钥签名的 `package.bin` 及其签名复制到临时目录
+    fn signed_package() -> (tempfile::TempDir, PathBuf) {
+        let temp = tempfile::tempdir().unwrap();
+        let archive = temp.path().join("update.zip");
+        fs::copy(fixture("package.bin"), &archive).unwrap();
+        fs::copy(
+            fixture("package.bin.minisig"),
+            default_signature_path(&archive),
+        )
+        .unwrap();
+        (temp, archive)
+    }
+
+    fn options(key: &str, required: bool) -> SignatureOptions {
+        SignatureOptions {
+            signature: None,
+            public_key: Some(fixture(key)),
+            required,
+        }
+    }
+
+    fn verify(
+        archive: &Path,
+        options: &SignatureOptions,
+    ) -> Result<SignatureStatus, SignatureError> {
+        verify_archive_signature(archive, &default_signature_path(archive), options, false)
+    }
+
+    #[test]
+    fn valid_signature_is_verified() {
+        let (_temp, archive) = signed_package();
+        assert_eq!(
+            verify(&archive, &options("test.pub", true)).unwrap(),
+            SignatureStatus::Verified
+        );
+    }
+
+    #[test]
+    fn tampered_archive_is_rejected() {
+        let (_temp, archive) = signed_package();
+        let mut content = fs::read(&archive).unwrap();
+        content[0] ^= 1;
+        fs::write(&archive, content).unwrap();
+
+        assert!(verify(&archive, &options("test.pub", false)).is_err());
+    }
+
+    #[test]
+    fn signature_from_another_key_is_rejected() {
+        let (_temp, archive) = signed_package();
+        assert!(verify(&archive, &options("other.pub", false)).is_err());
+    }
+
+    #[test]
+    fn missing_signature_is_skipped_unless_required() {
+        let (_temp, archive) = signed_package();
+        fs::remove_file(default_signature_path(&archive)).unwrap();
+
+        assert_eq!(
+            verify(&archive, &options("test.pub", false)).unwrap(),
+            SignatureStatus::Skipped
+        );
+        let error = verify(&archive, &options("test.pub", true)).unwrap_err();
+        assert!(error.0.contains("无法读取签名文件"), "{}", error);
+    }
+
+    #[test]
+    fn manifest_signature_covers_the_content_read() {
+        let manifest = fixture("manifest.json");
+        let content = fs::read(&manifest).unwrap();
+        let options = options("test.pub", true);
+
+        assert_eq!(
+            verify_manifest_signature(&manifest, &content, &options, false).unwrap(),
+            SignatureStatus::Verified
+        );
+        let forged = br#"{"version":"99.0.0","files":[]}"#;
+        assert!(verify_manifest_signature(&manifest, forged, &options, false).is_err());
+    }
+
+    #[test]
+    fn signature_errors_map_to_their_own_exit_code() {
+        let (_temp, archive) = signed_package();
+        let error = verify(&archive, &options("other.pub", false)).unwrap_err();
+        // 暂存阶段的错误带有失败阶段，退出码取决于其中的原因
+        let error = UpdateError {
+            phase: UpdatePhase::Verify,
+            source: Box::new(error),
+        };
+
+        assert_eq!(update_exit_code(&error), EXIT_CODE_SIGNATURE);
+        assert_eq!(EXIT_CODE_SIGNATURE, 3);
+        let other: Box<dyn Error> = "其他错误".into();
+        assert_eq!(update_exit_code(other.as_ref()), 1);
+    }
+}
@@ -0,0 +1,1752 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::UpdateOptions;
+use super::delta::{DeltaBaseMismatch, apply_delta};
+use super::journal::{JOURNAL_FILE_NAME, Journal, JournalRecord, JournalSummary};
+use super::manifest::{
+    INSTALLED_MANIFEST_NAME, ManifestFile, PackageManifest, locate_manifest, sha256_file,
+    to_manifest_path, verify_directory,
+};
+use super::migration::{SettingsMigration, plan_migrations};
+use super::pending::{
+    PENDING_DIR_NAME, PENDING_FILES_DIR, PENDING_LIST_NAME, PendingAction, PendingEntry,
+    PendingReplacements,
+};
+use super::plugins::{PluginCompatibility, PluginPlan, check_compatibility, plan_plugins};
+use super::preserve::{DEFAULT_PRESERVE_PATTERNS, HOST_EXE_NAME, PreserveRules};
+use super::prune::{is_empty_dir, parent_dirs, plan_prune};
+use super::report::UpdateReport;
+use super::retry::{RetryPolicy, is_locked, retry_io};
+use super::self_update::{is_running_host, staged_host};
+use super::signature::{SignatureStatus, default_signature_path, verify_archive_signature};
+use super::version::{APP_EXE_NAME, Version, VersionError, installed_version, read_exe_version};
+use crate::archive::extract::{StripComponents, extract_archive, list_archive};
+use crate::archive::safety::SafetyLimits;
+use crate::disk::{ensure_space, path_size};
+
+/// 更新过程中使用的工作目录名称（位于程序目录下，保证与目标文件同一卷以便原子重命名）
+pub const WORK_DIR_NAME: &str = ".update";
+
+const STAGING_DIR_NAME: &str = "staging";
+const BACKUP_DIR_NAME: &str = "backup";
+/// 附加更新包的解压目录前缀，校验后并入暂存目录
+const PACKAGE_DIR_PREFIX: &str = "package-";
+
+/// 工作目录中存放更新包副本的目录，签名校验与解压都针对副本进行
+const PACKAGES_DIR_NAME: &str = "packages";
+
+/// 更新事务所处的阶段，用于失败时报告具体出错位置
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdatePhase {
+    /// 检查磁盘空间
+    Preflight,
+    /// 准备工作目录
+    Prepare,
+    /// 解压到暂存目录
+    Stage,
+    /// 校验文件清单与版本
+    Verify,
+    /// 清理旧版本遗留文件
+    Prune,
+    /// 备份将被替换的文件
+    Backup,
+    /// 将暂存文件替换到程序目录
+    Swap,
+}
+
+impl fmt::Display for UpdatePhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            UpdatePhase::Preflight => "更新预检",
+            UpdatePhase::Prepare => "准备工作目录",
+            UpdatePhase::Stage => "解压到暂存目录",
+            UpdatePhase::Verify => "校验更新包",
+            UpdatePhase::Prune => "清理旧版本文件",
+            UpdatePhase::Backup => "备份原有文件",
+            UpdatePhase::Swap => "替换文件",
+        };
+        f.write_str(name)
+    }
+}
+
+/// 带有失败阶段信息的更新错误
+#[derive(Debug)]
+pub struct UpdateError {
+    pub phase: UpdatePhase,
+    pub source: Box<dyn Error>,
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.phase, self.source)
+    }
+}
+
+impl Error for UpdateError {}
+
+/// 为 `Result` 附加失败阶段
+pub trait PhaseContext<T> {
+    fn phase(self, phase: UpdatePhase) -> Result<T, UpdateError>;
+}
+
+impl<T, E: Into<Box<dyn Error>>> PhaseContext<T> for Result<T, E> {
+    fn phase(self, phase: UpdatePhase) -> Result<T, UpdateError> {
+        self.map_err(|e| UpdateError {
+            phase,
+            source: e.into(),
+        })
+    }
+}
+
+/// 已执行的文件系统操作，回滚时按逆序撤销
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Operation {
+    /// 原有文件或目录被移入备份目录
+    BackedUp { original: PathBuf, backup: PathBuf },
+    /// 为放置新文件而创建的目录
+    CreatedDir(PathBuf),
+    /// 清理后变空而被删除的目录
+    RemovedDir(PathBuf),
+    /// 从暂存目录移入程序目录的新文件
+    Installed(PathBuf),
+    /// 因原文件被占用而移入延迟替换目录的新文件或列表
+    Deferred(PathBuf),
+}
+
+/// 暂存并校验完成的更新包，由 `UpdateTransaction::install` 安装
+pub struct StagedUpdate {
+    /// 实际安装的更新包，记录在更新日志中
+    archive: PathBuf,
+    manifest: Option<PackageManifest>,
+    /// 安装后写入程序目录的安装记录
+    record: PackageManifest,
+    /// 所有更新包声明的设置迁移步骤
+    migrations: Vec<SettingsMigration>,
+}
+
+/// 更新事务：先解压到暂存目录，再备份并逐个原子替换，任一步失败都可回滚
+pub struct UpdateTransaction {
+    install_dir: PathBuf,
+    work_dir: PathBuf,
+    staging_dir: PathBuf,
+    backup_dir: PathBuf,
+    operations: Vec<Operation>,
+    /// 仍被占用、无法移到一旁的原有文件（相对路径），新文件改为延迟替换
+    locked: BTreeSet<PathBuf>,
+    /// 本次更新新增的延迟替换条目
+    pending: Vec<PendingEntry>,
+    retry: RetryPolicy,
+    journal: Option<Journal>,
+    report: UpdateReport,
+    /// 内容未变、留在原位的文件及其所在目录（相对路径），清理时不移入备份目录
+    unchanged: HashSet<PathBuf>,
+    /// 当前进程是程序目录中的宿主程序，新宿主程序需暂存后再替换
+    running_host: bool,
+    verbose: bool,
+}
+
+impl UpdateTransaction {
+    pub fn new(install_dir: &Path, verbose: bool) -> Self {
+        let work_dir = install_dir.join(WORK_DIR_NAME);
+        Self {
+            install_dir: install_dir.to_path_buf(),
+            staging_dir: work_dir.join(STAGING_DIR_NAME),
+            backup_dir: work_dir.join(BACKUP_DIR_NAME),
+            work_dir,
+            operations: Vec::new(),
+            locked: BTreeSet::new(),
+            pending: Vec::new(),
+            retry: RetryPolicy::default(),
+            journal: None,
+            report: UpdateReport::default(),
+            unchanged: HashSet::new(),
+            running_host: is_running_host(install_dir),
+            verbose,
+        }
+    }
+
+    /// 读取上次中断的更新日志，据此重建事务以便继续完成或回滚；没有未完成的更新时返回 `None`
+    pub fn load_unfinished(
+        install_dir: &Path,
+        verbose: bool,
+    ) -> Result<Option<(Self, JournalSummary)>, Box<dyn Error>> {
+        let mut transaction = Self::new(install_dir, verbose);
+        let path = transaction.work_dir.join(JOURNAL_FILE_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let records = Journal::read(&path)?;
+        let summary = JournalSummary::from_records(&records);
+        for record in records {
+            match record {
+                JournalRecord::Step { operation } => transaction.operations.push(operation),
+                JournalRecord::Deferred { entry } => {
+                    if entry.action == PendingAction::Replace {
+                        transaction.locked.insert(PathBuf::from(&entry.path));
+                    }
+                    transaction.pending.push(entry);
+                }
+                _ => {}
+            }
+        }
+
+        transaction.journal = Some(Journal::open(&path)?);
+        Ok(Some((transaction, summary)))
+    }
+
+    /// 在工作目录中预检、解压并校验更新包，检查版本、用户插件与设置迁移。
+    ///
+    /// 这一阶段不改动程序目录，因此在关闭程序之前执行：拒绝更新时程序保持运行。
+    /// 更新日志在安装时才创建，主程序在暂存期间不会把程序目录误判为更新未完成。
+    /// 出错时返回失败阶段，由调用方丢弃工作目录。
+    pub fn stage(
+        &mut self,
+        archive_path: &Path,
+        options: &UpdateOptions,
+    ) -> Result<StagedUpdate, UpdateError> {
+        self.preflight(archive_path, options)
+            .phase(UpdatePhase::Preflight)?;
+        self.prepare().phase(UpdatePhase::Prepare)?;
+
+        // 只解压通过签名校验的副本，原文件之后被替换也不影响本次更新
+        self.enter(UpdatePhase::Verify)?;
+        let main_signature = options
+            .signature
+            .signature
+            .clone()
+            .unwrap_or_else(|| default_signature_path(archive_path));
+        let (archive_copy, archive_signature) = self
+            .verified_copy(archive_path, &main_signature, 0, options)
+            .phase(UpdatePhase::Verify)?;
+        let fallback_copy = match options.fallback_archive.as_deref() {
+            Some(full) => Some(
+                self.verified_copy(full, &default_signature_path(full), 1, options)
+                    .phase(UpdatePhase::Verify)?,
+            ),
+            None => None,
+        };
+        let mut extra_copies = Vec::new();
+        for (index, extra) in options.extra_archives.iter().enumerate() {
+            extra_copies.push(
+                self.verified_copy(extra, &default_signature_path(extra), index + 2, options)
+                    .phase(UpdatePhase::Verify)?,
+            );
+        }
+
+        self.enter(UpdatePhase::Stage)?;
+        if self.verbose {
+            println!("📦 正在解压到暂存目录: {}", self.staging_dir.display());
+        }
+        self.extract(&archive_copy, options.strip_components)
+            .phase(UpdatePhase::Stage)?;
+
+        self.enter(UpdatePhase::Verify)?;
+        let mut source = archive_path;
+        let verified = self.verify(archive_path, archive_signature, options);
+        let manifest = match (
+            verified,
+            options.fallback_archive.as_deref(),
+            &fallback_copy,
+        ) {
+            (Err(e), Some(full), Some((full_copy, full_signature)))
+                if e.is::<DeltaBaseMismatch>() =>
+            {
+                println!("⚠️  {}", e);
+                println!("📦 改用完整更新包: {}", full.display());
+                source = full;
+                self.enter(UpdatePhase::Stage)?;
+                self.restage(full_copy, options.strip_components)
+                    .phase(UpdatePhase::Stage)?;
+                self.enter(UpdatePhase::Verify)?;
+                self.verify(full, *full_signature, options)
+                    .phase(UpdatePhase::Verify)?
+            }
+            (result, _, _) => result.phase(UpdatePhase::Verify)?,
+        };
+        let package_version = self
+            .check_version(manifest.as_ref(), options)
+            .phase(UpdatePhase::Verify)?;
+
+        let mut record = match &manifest {
+            Some(manifest) => manifest.to_installed_record(),
+            None => PackageManifest::from_directory(&self.staging_dir).phase(UpdatePhase::Stage)?,
+        };
+        record.version = package_version.map(|version| version.to_string());
+        let mut migrations = manifest
+            .as_ref()
+            .map(|m| m.settings_migrations.clone())
+            .unwrap_or_default();
+        self.report
+            .packages
+            .push(describe_package(source, record.version.as_deref()));
+
+        // 附加更新包各自解压校验后并入暂存目录，之后与主更新包一起备份与替换
+        let extras = options.extra_archives.iter().zip(&extra_copies);
+        for (index, (extra, (copy, signature))) in extras.enumerate() {
+            let extra_record = self.stage_extra(extra, copy, *signature, index + 1, options)?;
+            self.report
+                .packages
+                .push(describe_package(extra, extra_record.version.as_deref()));
+            record.files.extend(extra_record.files);
+            migrations.extend(extra_record.settings_migrations);
+        }
+
+        if let Some(compatibility) = manifest
+            .as_ref()
+            .and_then(|m| m.plugin_compatibility.as_ref())
+            && let Some(plugins_dir) = &options.plugins_dir
+        {
+            self.check_plugins(plugins_dir, compatibility)
+                .phase(UpdatePhase::Verify)?;
+        }
+        if !migrations.is_empty()
+            && let Some(settings_dir) = &options.settings_dir
+        {
+            self.plan_settings_migrations(settings_dir, &migrations)
+                .phase(UpdatePhase::Verify)?;
+        }
+
+        Ok(StagedUpdate {
+            archive: source.to_path_buf(),
+            manifest,
+            record,
+            migrations,
+        })
+    }
+
+    /// 开始记录更新日志，备份并替换程序目录中的文件，需在关闭程序之后执行；出错时返回失败阶段，由调用方决定是否回滚
+    pub fn install(
+        &mut self,
+        staged: StagedUpdate,
+        options: &UpdateOptions,
+    ) -> Result<(), UpdateError> {
+        let StagedUpdate {
+            archive,
+            manifest,
+            record,
+            migrations,
+        } = staged;
+        self.begin(&archive).phase(UpdatePhase::Prepare)?;
+
+        // 主程序退出时会保存设置，按退出后的设置文件重新演算迁移
+        if !migrations.is_empty()
+            && let Some(settings_dir) = &options.settings_dir
+        {
+            self.enter(UpdatePhase::Verify)?;
+            self.plan_settings_migrations(settings_dir, &migrations)
+                .phase(UpdatePhase::Verify)?;
+        }
+
+        record
+            .write(&self.staging_dir.join(INSTALLED_MANIFEST_NAME))
+            .phase(UpdatePhase::Stage)?;
+
+        let mut entries = collect_entries(&self.staging_dir).phase(UpdatePhase::Stage)?;
+        let planned = entries
+            .iter()
+            .filter(|entry| !entry.is_dir)
+            .map(|entry| to_manifest_path(&entry.relative))
+            .collect();
+        self.append(&JournalRecord::Planned { files: planned })
+            .phase(UpdatePhase::Stage)?;
+
+        if options.clean {
+            self.enter(UpdatePhase::Backup)?;
+            let mut patterns: Vec<String> = DEFAULT_PRESERVE_PATTERNS
+                .iter()
+                .map(|p| p.to_string())
+                .collect();
+            patterns.extend(options.preserve.iter().cloned());
+            if let Some(manifest) = &manifest {
+                patterns.extend(manifest.preserve.iter().cloned());
+            }
+            // 用户自行放入的插件不属于任何版本，清理时同样保留
+            let plugins = self
+                .plan_plugins(manifest.as_ref())
+                .phase(UpdatePhase::Backup)?;
+            patterns.extend(plugins.kept.iter().map(|folder| globset::escape(folder)));
+            self.report_plugins(plugins);
+
+            let rules =
+                PreserveRules::new(&self.install_dir, &patterns, options.data_dir.as_deref())
+                    .phase(UpdatePhase::Backup)?;
+            if self.verbose {
+                println!("🛡️  清理时保留: {}", rules.describe());
+            }
+            self.skip_unchanged(&mut entries, &record)
+                .phase(UpdatePhase::Backup)?;
+            self.backup_for_clean(&rules, Path::new(""))
+                .phase(UpdatePhase::Backup)?;
+        } else {
+            self.enter(UpdatePhase::Prune)?;
+            self.sync_plugins(manifest.as_ref())
+                .phase(UpdatePhase::Prune)?;
+            self.prune_stale(&record).phase(UpdatePhase::Prune)?;
+
+            self.enter(UpdatePhase::Backup)?;
+            self.skip_unchanged(&mut entries, &record)
+                .phase(UpdatePhase::Backup)?;
+        }
+
+        for entry in &entries {
+            self.backup_conflicts(entry).phase(UpdatePhase::Backup)?;
+        }
+
+        self.backup_pending().phase(UpdatePhase::Backup)?;
+
+        self.enter(UpdatePhase::Swap)?;
+        for entry in &entries {
+            self.install_entry(entry).phase(UpdatePhase::Swap)?;
+        }
+
+        self.write_pending(&record).phase(UpdatePhase::Swap)?;
+        Ok(())
+    }
+
+    /// 继续完成中断在替换阶段的更新：原有文件均已备份，只需把暂存目录中剩余的文件移入程序目录
+    pub fn finish(&mut self) -> Result<(), UpdateError> {
+        if self.staging_dir.is_dir() {
+            let entries = collect_entries(&self.staging_dir).phase(UpdatePhase::Swap)?;
+            for entry in &entries {
+                self.install_entry(entry).phase(UpdatePhase::Swap)?;
+            }
+        }
+
+        let record = PackageManifest::load_installed(&self.install_dir)
+            .phase(UpdatePhase::Swap)?
+            .unwrap_or_default();
+        self.write_pending(&record).phase(UpdatePhase::Swap)
+    }
+
+    /// 提交事务，删除暂存与备份文件，返回更新报告
+    pub fn commit(mut self) -> UpdateReport {
+        // 先删除日志，避免清理到一半断电时被误判为未完成的更新而回滚
+        if let Err(e) = self.remove_journal() {
+            println!("⚠️  删除更新日志失败: {}", e);
+        }
+        if let Err(e) = fs::remove_dir_all(&self.work_dir) {
+            println!(
+                "⚠️  清理更新工作目录失败: {} ({})",
+                self.work_dir.display(),
+                e
+            );
+        }
+        self.report
+    }
+
+    /// 丢弃暂存阶段的工作目录；暂存阶段不改动程序目录，也尚未创建更新日志，无需还原
+    pub fn discard(self) {
+        let _ = fs::remove_dir_all(&self.work_dir);
+    }
+
+    /// 按逆序撤销已执行的操作，将程序目录恢复到更新前的状态
+    pub fn rollback(mut self) -> Result<(), Box<dyn Error>> {
+        let mut failures = Vec::new();
+
+        while let Some(operation) = self.operations.pop() {
+            let result = match &operation {
+                Operation::Installed(path) | Operation::Deferred(path) => remove_path(path),
+                Operation::CreatedDir(path) => match fs::remove_dir(path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                    _ => Ok(()),
+                },
+                Operation::RemovedDir(path) => fs::create_dir_all(path),
+                // 日志先于操作写入，备份不存在说明操作未真正执行
+                Operation::BackedUp { original, backup } => match fs::symlink_metadata(backup) {
+                    Ok(_) => fs::rename(backup, original),
+                    Err(_) => Ok(()),
+                },
+            };
+
+            if let Err(e) = result {
+                let path = match &operation {
+                    Operation::Installed(path)
+                    | Operation::Deferred(path)
+                    | Operation::CreatedDir(path)
+                    | Operation::RemovedDir(path) => path,
+                    Operation::BackedUp { original, .. } => original,
+                };
+                failures.push(format!("{} ({})", path.display(), e));
+            } else if self.verbose
+                && let Operation::BackedUp { original, .. } = &operation
+            {
+                println!("↩️  已还原: {}", original.display());
+            }
+        }
+
+        if !failures.is_empty() {
+            return Err(format!(
+                "以下路径未能还原，备份保留在 {}:\n  {}",
+                self.backup_dir.display(),
+                failures.join("\n  ")
+            )
+            .into());
+        }
+
+        self.remove_journal()?;
+        let _ = fs::remove_dir_all(&self.work_dir);
+        Ok(())
+    }
+
+    fn remove_journal(&mut self) -> io::Result<()> {
+        self.journal = None;
+        match fs::remove_file(self.work_dir.join(JOURNAL_FILE_NAME)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn begin(&mut self, archive_path: &Path) -> io::Result<()> {
+        self.journal = Some(Journal::create(&self.work_dir.join(JOURNAL_FILE_NAME))?);
+        self.append(&JournalRecord::Begin {
+            archive: archive_path.to_path_buf(),
+            started_at: Local::now().to_rfc3339(),
+        })
+    }
+
+    fn append(&mut self, record: &JournalRecord) -> io::Result<()> {
+        match &mut self.journal {
+            Some(journal) => journal.append(record),
+            None => Ok(()),
+        }
+    }
+
+    /// 记录进入新阶段
+    fn enter(&mut self, phase: UpdatePhase) -> Result<(), UpdateError> {
+        self.append(&JournalRecord::Phase { phase }).phase(phase)
+    }
+
+    /// 在执行文件系统操作之前写入日志，再加入回滚列表
+    fn record(&mut self, operation: Operation) -> io::Result<()> {
+        self.append(&JournalRecord::Step {
+            operation: operation.clone(),
+        })?;
+        self.operations.push(operation);
+        Ok(())
+    }
+
+    fn defer(&mut self, entry: PendingEntry) -> io::Result<()> {
+        self.append(&JournalRecord::Deferred {
+            entry: entry.clone(),
+        })?;
+        if entry.action == PendingAction::Replace {
+            self.locked.insert(PathBuf::from(&entry.path));
+        }
+        self.pending.push(entry);
+        Ok(())
+    }
+
+    /// 在触碰程序目录之前，按清单校验暂存目录中的每个文件
+    fn verify(
+        &self,
+        archive_path: &Path,
+        archive_signature: SignatureStatus,
+        options: &UpdateOptions,
+    ) -> Result<Option<PackageManifest>, Box<dyn Error>> {
+        let located = locate_manifest(
+            options.manifest.as_deref(),
+            archive_path,
+            archive_signature,
+            &self.staging_dir,
+            &options.signature,
+            self.verbose,
+        )?;
+
+        let Some((manifest, source)) = located else {
+            if options.require_manifest {
+                return Err("未找到更新清单，已要求必须校验清单 (--require-manifest)".into());
+            }
+            println!("⚠️  未找到更新清单，跳过文件校验");
+            return Ok(None);
+        };
+
+        if self.verbose {
+            println!(
+                "🔍 正在校验文件清单 ({}, {} 个文件)",
+                source,
+                manifest.files.len()
+            );
+        }
+        if let Some(delta) = &manifest.delta {
+            if self.verbose {
+                println!("🩹 正在应用差分包 ({} 个补丁)", delta.patches.len());
+            }
+            apply_delta(
+                &manifest,
+                delta,
+                &self.install_dir,
+                installed_version(&self.install_dir)?.as_ref(),
+                &self.staging_dir,
+                self.verbose,
+            )?;
+        }
+        verify_directory(&manifest, &self.staging_dir)?;
+        println!("✅ 文件清单校验通过: {} 个文件", manifest.files.len());
+        Ok(Some(manifest))
+    }
+
+    /// 比较已安装版本与更新包版本，未经允许时拒绝降级或同版本重新安装。
+    ///
+    /// 版本优先取自清单与安装记录中的 `version`，其次取自 `STranslate.exe` 的版本资源。
+    fn check_version(
+        &mut self,
+        manifest: Option<&PackageManifest>,
+        options: &UpdateOptions,
+    ) -> Result<Option<Version>, Box<dyn Error>> {
+        let package = match manifest.and_then(|m| m.version.as_deref()) {
+            Some(text) => Some(
+                Version::parse(text).ok_or_else(|| format!("更新清单中的版本号无效: {}", text))?,
+            ),
+            None => read_exe_version(&self.staging_dir.join(APP_EXE_NAME))?,
+        };
+        let installed = installed_version(&self.install_dir)?;
+
+        self.report.installed_version = installed.as_ref().map(Version::to_string);
+        self.report.package_version = package.as_ref().map(Version::to_string);
+        println!(
+            "🏷️  已安装版本: {}，更新包版本: {}",
+            self.report.installed_version.as_deref().unwrap_or("未知"),
+            self.report.package_version.as_deref().unwrap_or("未知")
+        );
+
+        let (Some(installed), Some(package)) = (installed, package.clone()) else {
+            println!("⚠️  无法确定版本，跳过版本检查");
+            return Ok(package);
+        };
+
+        if (package < installed && !options.allow_downgrade)
+            || (package == installed && !options.reinstall)
+        {
+            return Err(Box::new(VersionError { installed, package }));
+        }
+        if package < installed {
+            println!("⚠️  正在降级: {} → {}", installed, package);
+        }
+        Ok(Some(package))
+    }
+
+    /// 在替换任何文件之前检查用户插件能否在新版本中使用，结果记录在报告中
+    fn check_plugins(
+        &mut self,
+        plugins_dir: &Path,
+        compatibility: &PluginCompatibility,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.verbose {
+            println!("🧩 正在检查用户插件兼容性: {}", plugins_dir.display());
+        }
+        let check = check_compatibility(plugins_dir, compatibility)?;
+        if check.incompatible.is_empty() {
+            if self.verbose && check.unknown.is_empty() {
+                println!("✅ 用户插件均与新版本兼容");
+            }
+        } else {
+            println!(
+                "⚠️  以下 {} 个用户插件与新版本不兼容:",
+                check.incompatible.len()
+            );
+            for plugin in &check.incompatible {
+                println!(
+                    "   - {} v{} ({})",
+                    plugin.name, plugin.version, plugin.reason
+                );
+            }
+        }
+        if !check.unknown.is_empty() {
+            println!(
+                "❔ 以下 {} 个用户插件未声明接口版本，无法判断是否兼容:",
+                check.unknown.len()
+            );
+            for plugin in &check.unknown {
+                println!("   - {} v{}", plugin.name, plugin.version);
+            }
+        }
+        self.report.incompatible_plugins = check.incompatible;
+        self.report.unknown_plugins = check.unknown;
+        Ok(())
+    }
+
+    /// 在替换文件之前按更新前的版本演算设置迁移，迁移步骤有误时中止更新；结果在提交后写入
+    fn plan_settings_migrations(
+        &mut self,
+        settings_dir: &Path,
+        steps: &[SettingsMigration],
+    ) -> Result<(), Box<dyn Error>> {
+        let installed = self
+            .report
+            .installed_version
+            .as_deref()
+            .and_then(Version::parse);
+        if installed.is_none() {
+            println!("⚠️  无法确定更新前的版本，跳过限定版本范围的设置迁移");
+        }
+        let plan = plan_migrations(settings_dir, steps, installed.as_ref())?;
+        if self.verbose {
+            println!(
+                "🔄 设置迁移: {} 项将执行，{} 项跳过",
+                plan.applied.len(),
+                plan.skipped.len()
+            );
+        }
+        self.report.settings_migration = Some(plan);
+        Ok(())
+    }
+
+    /// 将更新包复制到工作目录并校验副本的签名，返回之后用于解压的副本路径。
+    ///
+    /// 原文件可能位于当前用户可写的下载目录，校验后再从原路径解压会留下被替换的窗口。
+    fn verified_copy(
+        &self,
+        archive_path: &Path,
+        signature_path: &Path,
+        index: usize,
+        options: &UpdateOptions,
+    ) -> Result<(PathBuf, SignatureStatus), Box<dyn Error>> {
+        if archive_path.is_dir() {
+            let status = verify_archive_signature(
+                archive_path,
+                signature_path,
+                &options.signature,
+                self.verbose,
+            )?;
+            return Ok((archive_path.to_path_buf(), status));
+        }
+
+        let dir = self.work_dir.join(PACKAGES_DIR_NAME);
+        fs::create_dir_all(&dir)?;
+        let mut name = OsString::from(format!("{}-", index));
+        name.push(archive_path.file_name().unwrap_or_default());
+        let copy = dir.join(name);
+        fs::copy(archive_path, &copy)
+            .map_err(|e| format!("复制更新包失败: {} ({})", archive_path.display(), e))?;
+        let status =
+            verify_archive_signature(&copy, signature_path, &options.signature, self.verbose)?;
+        if status == SignatureStatus::Skipped
+            && options
+                .unverified_downloads
+                .iter()
+                .any(|path| path == archive_path)
+        {
+            if !options.allow_unverified {
+                return Err(format!(
+                    "下载的更新包既未通过 --sha256 校验也未通过签名校验，拒绝更新（使用 --allow-unverified 强制更新）: {}",
+                    archive_path.display()
+                )
+                .into());
+            }
+            println!(
+                "⚠️  下载的更新包未经 --sha256 或签名校验 (--allow-unverified): {}",
+                archive_path.display()
+            );
+        }
+        Ok((copy, status))
+    }
+
+    fn extract(&self, archive_path: &Path, strip: StripComponents) -> Result<(), Box<dyn Error>> {
+        self.extract_to(archive_path, &self.staging_dir, strip)
+    }
+
+    fn extract_to(
+        &self,
+        archive_path: &Path,
+        destination: &Path,
+        strip: StripComponents,
+    ) -> Result<(), Box<dyn Error>> {
+        extract_archive(
+            archive_path,
+            destination,
+            &SafetyLimits::default(),
+            strip,
+            self.verbose,
+        )?;
+        Ok(())
+    }
+
+    /// 解压并校验附加更新包，返回其安装记录（含设置迁移步骤）；
+    /// 附加更新包不参与版本比较，也不支持差分包。
+    /// `--strip-components` 只作用于主更新包：附加更新包（例如只含 `Plugins/` 的插件包）按原样解压
+    fn stage_extra(
+        &mut self,
+        archive_path: &Path,
+        copy: &Path,
+        signature: SignatureStatus,
+        index: usize,
+        options: &UpdateOptions,
+    ) -> Result<PackageManifest, UpdateError> {
+        let dir = self
+            .work_dir
+            .join(format!("{}{}", PACKAGE_DIR_PREFIX, index));
+        self.enter(UpdatePhase::Stage)?;
+        if self.verbose {
+            println!("📦 正在解压附加更新包: {}", archive_path.display());
+        }
+        fs::create_dir_all(&dir).phase(UpdatePhase::Stage)?;
+        self.extract_to(copy, &dir, StripComponents::default())
+            .phase(UpdatePhase::Stage)?;
+
+        self.enter(UpdatePhase::Verify)?;
+        let record = verify_extra(archive_path, signature, &dir, options, self.verbose)
+            .phase(UpdatePhase::Verify)?;
+        self.merge_staged(archive_path, &dir)
+            .phase(UpdatePhase::Stage)?;
+        Ok(record)
+    }
+
+    /// 将附加更新包的文件移入暂存目录，与已暂存的文件重复时拒绝更新
+    fn merge_staged(&self, archive_path: &Path, dir: &Path) -> Result<(), Box<dyn Error>> {
+        for entry in collect_entries(dir)? {
+            let target = self.staging_dir.join(&entry.relative);
+            if entry.is_dir {
+                fs::create_dir_all(&target)?;
+                continue;
+            }
+            if fs::symlink_metadata(&target).is_ok() {
+                return Err(format!(
+                    "更新包 {} 与其他更新包包含相同的文件: {}",
+                    archive_path.display(),
+                    to_manifest_path(&entry.relative)
+                )
+                .into());
+            }
+            fs::rename(dir.join(&entry.relative), &target)?;
+        }
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    /// 清空暂存目录并重新解压（差分包无法应用时改用完整包）
+    fn restage(&self, archive_path: &Path, strip: StripComponents) -> Result<(), Box<dyn Error>> {
+        fs::remove_dir_all(&self.staging_dir)?;
+        fs::create_dir_all(&self.staging_dir)?;
+        self.extract(archive_path, strip)
+    }
+
+    /// 对比上一版本的安装记录，删除旧版本独有且未被修改的文件
+    fn prune_stale(&mut self, record: &PackageManifest) -> Result<(), Box<dyn Error>> {
+        let Some(previous) = PackageManifest::load_installed(&self.install_dir)? else {
+            if self.verbose {
+                println!("ℹ️  未找到上一版本的安装记录，跳过旧文件清理");
+            }
+            return Ok(());
+        };
+
+        let new_files: BTreeSet<String> = record.files.iter().map(|f| f.path.clone()).collect();
+        let plan = plan_prune(&self.install_dir, &previous, &new_files)?;
+
+        let mut removed = Vec::new();
+        for path in plan.stale {
+            if self.try_move_to_backup(Path::new(&path))? {
+                removed.push(path);
+            } else {
+                self.defer_removal(Path::new(&path))?;
+            }
+        }
+        for dir in parent_dirs(&removed) {
+            let prefix = format!("{}/", dir);
+            let still_used = new_files.iter().any(|f| f.starts_with(&prefix));
+            let path = self.install_dir.join(&dir);
+            if !still_used && is_empty_dir(&path) {
+                self.record(Operation::RemovedDir(path.clone()))?;
+                fs::remove_dir(&path).map_err(|e| with_path(e, &path))?;
+            }
+        }
+
+        self.report.removed = removed;
+        self.report.kept_modified = plan.modified;
+        self.report.unknown = plan.unknown;
+        Ok(())
+    }
+
+    fn plan_plugins(
+        &self,
+        manifest: Option<&PackageManifest>,
+    ) -> Result<PluginPlan, Box<dyn Error>> {
+        let previous = PackageManifest::load_installed(&self.install_dir)?;
+        let pre_plugin_ids = manifest.map_or(&[][..], |m| &m.pre_plugin_ids);
+        plan_plugins(
+            &self.install_dir,
+            &self.staging_dir,
+            previous.as_ref(),
+            pre_plugin_ids,
+        )
+    }
+
+    /// 以插件目录为单位更新预装插件：新版本包含或已不再包含的预装插件整体移入备份目录，
+    /// 避免改名或删除的旧文件残留在插件目录中
+    fn sync_plugins(&mut self, manifest: Option<&PackageManifest>) -> Result<(), Box<dyn Error>> {
+        let plan = self.plan_plugins(manifest)?;
+        for folder in plan.replaced.iter().chain(&plan.removed) {
+            let relative = Path::new(folder);
+            if !self.try_move_to_backup(relative)? {
+                self.backup_plugin_files(relative)?;
+            }
+        }
+        self.report_plugins(plan);
+        Ok(())
+    }
+
+    /// 插件目录中有被占用的文件时逐个处理：新版本中同路径的文件留给替换阶段，其余文件延迟删除
+    fn backup_plugin_files(&mut self, relative: &Path) -> io::Result<()> {
+        for entry in fs::read_dir(self.install_dir.join(relative))? {
+            let entry = entry?;
+            let child = relative.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                if !self.try_move_to_backup(&child)? {
+                    self.backup_plugin_files(&child)?;
+                }
+            } else if !self.staging_dir.join(&child).is_file()
+                && !self.try_move_to_backup(&child)?
+            {
+                self.defer_removal(&child)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn report_plugins(&mut self, plan: PluginPlan) {
+        if self.verbose {
+            for folder in &plan.kept {
+                println!("🧩 保留非预装插件: {}", folder);
+            }
+        }
+        self.report.plugins_replaced = plan.replaced;
+        self.report.plugins_removed = plan.removed;
+        self.report.plugins_kept = plan.kept;
+    }
+
+    /// 在关闭程序、写入任何文件之前检查程序目录可写，且所在磁盘能否容纳解压后的更新包。
+    ///
+    /// 被替换的文件在提交前移入同一磁盘上的备份目录，替换本身不释放空间，
+    /// 因此只扣除准备阶段会删除的残留工作目录。
+    fn preflight(
+        &self,
+        archive_path: &Path,
+        options: &UpdateOptions,
+    ) -> Result<(), Box<dyn Error>> {
+        let limits = SafetyLimits::default();
+        let unpacked = |path: &Path| -> Result<u64, Box<dyn Error>> {
+            Ok(list_archive(path, &limits)?
+                .iter()
+                .filter(|entry| !entry.is_dir)
+                .map(|entry| entry.size)
+                .sum())
+        };
+
+        // 改用完整更新包时会先清空暂存目录，峰值取两者中较大者
+        let mut incoming = unpacked(archive_path)?;
+        if let Some(full) = &options.fallback_archive {
+            incoming = incoming.max(unpacked(full)?);
+        }
+        for extra in &options.extra_archives {
+            incoming += unpacked(extra)?;
+        }
+        // 压缩包先复制到工作目录再解压，副本同样占用空间
+        let archives = std::iter::once(archive_path)
+            .chain(options.fallback_archive.as_deref())
+            .chain(options.extra_archives.iter().map(PathBuf::as_path));
+        for archive in archives.filter(|archive| !archive.is_dir()) {
+            incoming += fs::metadata(archive)?.len();
+        }
+        let reclaimed = path_size(&self.work_dir)?;
+        ensure_space(&self.install_dir, incoming, reclaimed, self.verbose)?;
+        ensure_writable(&self.install_dir)
+    }
+
+    fn prepare(&self) -> io::Result<()> {
+        if self.work_dir.exists() {
+            if self.verbose {
+                println!("🧹 清理残留的更新工作目录: {}", self.work_dir.display());
+            }
+            fs::remove_dir_all(&self.work_dir)?;
+        }
+        fs::create_dir_all(&self.staging_dir)?;
+        fs::create_dir_all(&self.backup_dir)
+    }
+
+    /// 清理模式：将保留规则之外的条目移入备份目录，含有保留条目的目录逐层处理
+    fn backup_for_clean(&mut self, rules: &PreserveRules, relative: &Path) -> io::Result<()> {
+        for entry in fs::read_dir(self.install_dir.join(relative))? {
+            let entry = entry?;
+            let child = relative.join(entry.file_name());
+
+            if rules.is_preserved(&child) {
+                if self.verbose {
+                    println!("🛡️  保留: {}", child.display());
+                }
+                continue;
+            }
+
+            let is_dir = entry.file_type()?.is_dir();
+            if self.unchanged.contains(&child) {
+                // 内容未变的文件留在原位，其所在目录逐层处理
+                if is_dir {
+                    self.backup_for_clean(rules, &child)?;
+                }
+                continue;
+            }
+            if is_dir && rules.contains_preserved(&self.install_dir, &child)? {
+                self.backup_for_clean(rules, &child)?;
+            } else if !self.try_move_to_backup(&child)? {
+                // 目录中有被占用的文件时逐项处理，只延迟被占用的文件
+                if is_dir {
+                    self.backup_for_clean(rules, &child)?;
+                } else {
+                    self.defer_removal(&child)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 按安装记录中的大小与 SHA-256 对比程序目录中的现有文件，内容相同的条目从暂存目录删除，
+    /// 不再备份与替换（清理模式下也留在原位），也不会因文件被占用而延迟替换
+    fn skip_unchanged(
+        &mut self,
+        entries: &mut Vec<StagedEntry>,
+        record: &PackageManifest,
+    ) -> io::Result<()> {
+        let expected: BTreeMap<&str, &ManifestFile> = record
+            .files
+            .iter()
+            .map(|file| (file.path.as_str(), file))
+            .collect();
+
+        let mut unchanged = HashSet::new();
+        for entry in entries.iter().filter(|entry| !entry.is_dir) {
+            let path = to_manifest_path(&entry.relative);
+            let Some(file) = expected.get(path.as_str()) else {
+                continue;
+            };
+            let target = self.install_dir.join(&entry.relative);
+            let same_size = fs::symlink_metadata(&target)
+                .is_ok_and(|metadata| metadata.is_file() && metadata.len() == file.size);
+            if same_size && sha256_file(&target)?.eq_ignore_ascii_case(&file.sha256) {
+                unchanged.insert(entry.relative.clone());
+            }
+        }
+
+        for relative in &unchanged {
+            let staged = self.staging_dir.join(relative);
+            fs::remove_file(&staged).map_err(|e| with_path(e, &staged))?;
+            if self.verbose {
+                println!("⏭️  内容未变，跳过: {}", relative.display());
+            }
+        }
+        entries.retain(|entry| !unchanged.contains(&entry.relative));
+        self.report.unchanged = unchanged.len();
+
+        for relative in unchanged {
+            self.unchanged
+                .extend(relative.ancestors().skip(1).map(Path::to_path_buf));
+            self.unchanged.insert(relative);
+        }
+        Ok(())
+    }
+
+    /// 将与新文件冲突的原有路径移入备份目录（包括挡路的同名文件或目录）
+    fn backup_conflicts(&mut self, entry: &StagedEntry) -> io::Result<()> {
+        if self.is_running_host(&entry.relative) {
+            return Ok(());
+        }
+        let mut current = PathBuf::new();
+        let mut components = entry.relative.components().peekable();
+
+        while let Some(component) = components.next() {
+            current.push(component);
+            let target = self.install_dir.join(&current);
+            let is_last = components.peek().is_none();
+
+            let Ok(metadata) = fs::symlink_metadata(&target) else {
+                break;
+            };
+
+            let conflict = if is_last {
+                !(entry.is_dir && metadata.is_dir())
+            } else {
+                !metadata.is_dir()
+            };
+
+            if conflict {
+                if is_last && !entry.is_dir {
+                    if !self.try_move_to_backup(&current)? {
+                        self.defer(PendingEntry {
+                            path: to_manifest_path(&current),
+                            action: PendingAction::Replace,
+                        })?;
+                    }
+                } else {
+                    self.move_to_backup(&current)?;
+                }
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn install_entry(&mut self, entry: &StagedEntry) -> io::Result<()> {
+        let target = self.install_dir.join(&entry.relative);
+
+        if entry.is_dir {
+            return self.create_dirs(&entry.relative);
+        }
+        if self.locked.contains(&entry.relative) {
+            return self.defer_replacement(entry);
+        }
+        if self.is_running_host(&entry.relative) {
+            return self.stage_host(entry);
+        }
+
+        if let Some(parent) = entry.relative.parent() {
+            self.create_dirs(parent)?;
+        }
+
+        self.record(Operation::Installed(target.clone()))?;
+        move_file(self.retry, &self.staging_dir.join(&entry.relative), &target)
+            .map_err(|e| with_path(e, &target))?;
+        self.report.written += 1;
+
+        if self.verbose {
+            println!("📝 已更新: {}", target.display());
+        }
+        Ok(())
+    }
+
+    /// 正在运行的宿主程序无法被覆盖，新宿主程序先以临时名称放在旁边，提交后再替换
+    fn stage_host(&mut self, entry: &StagedEntry) -> io::Result<()> {
+        let target = staged_host(&self.install_dir);
+        // 上次未完成替换的暂存文件先移入备份目录，回滚时恢复
+        if let Some(name) = target.file_name()
+            && fs::symlink_metadata(&target).is_ok()
+        {
+            self.move_to_backup(Path::new(name))?;
+        }
+
+        self.record(Operation::Installed(target.clone()))?;
+        move_file(self.retry, &self.staging_dir.join(&entry.relative), &target)
+            .map_err(|e| with_path(e, &target))?;
+        self.report.written += 1;
+        self.report.host_staged = true;
+
+        if self.verbose {
+            println!("⏳ 已暂存新的宿主程序: {}", target.display());
+        }
+        Ok(())
+    }
+
+    fn is_running_host(&self, relative: &Path) -> bool {
+        self.running_host
+            && relative
+                .to_str()
+                .is_some_and(|path| path.eq_ignore_ascii_case(HOST_EXE_NAME))
+    }
+
+    fn create_dirs(&mut self, relative: &Path) -> io::Result<()> {
+        let mut current = self.install_dir.clone();
+        for component in relative.components() {
+            current.push(component);
+            if !current.is_dir() {
+                self.record(Operation::CreatedDir(current.clone()))?;
+                fs::create_dir(&current).map_err(|e| with_path(e, &current))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 原有文件被占用：新文件移入延迟替换目录，下次启动时再替换
+    fn defer_replacement(&mut self, entry: &StagedEntry) -> io::Result<()> {
+        let relative = Path::new(PENDING_DIR_NAME)
+            .join(PENDING_FILES_DIR)
+            .join(&entry.relative);
+        if let Some(parent) = relative.parent() {
+            self.create_dirs(parent)?;
+        }
+
+        let target = self.install_dir.join(&relative);
+        self.record(Operation::Deferred(target.clone()))?;
+        move_file(self.retry, &self.staging_dir.join(&entry.relative), &target)
+            .map_err(|e| with_path(e, &target))
+    }
+
+    /// 需要删除的旧文件被占用：记录下来，下次启动时再删除
+    fn defer_removal(&mut self, relative: &Path) -> io::Result<()> {
+        self.defer(PendingEntry {
+            path: to_manifest_path(relative),
+            action: PendingAction::Remove,
+        })
+    }
+
+    /// 上次遗留的延迟替换将被本次更新取代，先移入备份目录以便回滚
+    fn backup_pending(&mut self) -> io::Result<()> {
+        for name in [PENDING_LIST_NAME, PENDING_FILES_DIR] {
+            let relative = Path::new(PENDING_DIR_NAME).join(name);
+            if fs::symlink_metadata(self.install_dir.join(&relative)).is_ok() {
+                self.move_to_backup(&relative)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 写入延迟替换列表：保留上次遗留且新版本中不存在的删除项，加上本次新增的条目
+    fn write_pending(&mut self, record: &PackageManifest) -> Result<(), Box<dyn Error>> {
+        let previous_list = self
+            .backup_dir
+            .join(PENDING_DIR_NAME)
+            .join(PENDING_LIST_NAME);
+        let mut entries: Vec<PendingEntry> = if previous_list.is_file() {
+            let content = fs::read_to_string(&previous_list)?;
+            serde_json::from_str::<PendingReplacements>(&content)
+                .map(|pending| pending.entries)
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        entries.retain(|entry| {
+            entry.action == PendingAction::Remove
+                && !record.files.iter().any(|f| f.path == entry.path)
+                && !self.pending.iter().any(|p| p.path == entry.path)
+        });
+
+        // 同一路径既待删除又待替换时（清理模式），以替换为准
+        for entry in &self.pending {
+            let replaced = entry.action == PendingAction::Remove
+                && self
+                    .pending
+                    .iter()
+                    .any(|p| p.path == entry.path && p.action == PendingAction::Replace);
+            if replaced {
+                continue;
+            }
+
+            match entry.action {
+                PendingAction::Replace => {
+                    println!("⏸️  文件被占用，将在下次启动时替换: {}", entry.path);
+                    self.report.deferred.push(entry.path.clone());
+                }
+                PendingAction::Remove => {
+                    println!("⏸️  文件被占用，将在下次启动时删除: {}", entry.path);
+                    self.report
+                        .deferred
+                        .push(format!("{}（待删除）", entry.path));
+                }
+            }
+            entries.push(entry.clone());
+        }
+
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        self.create_dirs(Path::new(PENDING_DIR_NAME))?;
+        let list = self
+            .install_dir
+            .join(PENDING_DIR_NAME)
+            .join(PENDING_LIST_NAME);
+        self.record(Operation::Deferred(list.clone()))?;
+        PendingReplacements { entries }.write(&list)
+    }
+
+    fn move_to_backup(&mut self, relative: &Path) -> io::Result<()> {
+        if self.try_move_to_backup(relative)? {
+            Ok(())
+        } else {
+            let original = self.install_dir.join(relative);
+            Err(io::Error::new(
+                io::ErrorKind::ResourceBusy,
+                format!("{}: 文件被占用", original.display()),
+            ))
+        }
+    }
+
+    /// 将原有路径重命名到备份目录，占用时按退避策略重试；仍被占用时返回 `false`
+    fn try_move_to_backup(&mut self, relative: &Path) -> io::Result<bool> {
+        let original = self.install_dir.join(relative);
+        let backup = self.backup_dir.join(relative);
+
+        if let Some(parent) = backup.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.record(Operation::BackedUp {
+            original: original.clone(),
+            backup: backup.clone(),
+        })?;
+        match retry_io(self.retry, || fs::rename(&original, &backup)) {
+            Ok(()) => {}
+            Err(e) if is_locked(&e) => {
+                // 未能移动，撤销时无需处理（日志中的记录在回滚时会因备份不存在而跳过）
+                self.operations.pop();
+                return Ok(false);
+            }
+            Err(e) => return Err(with_path(e, &original)),
+        }
+
+        if self.verbose {
+            println!("🗄️  已备份: {}", original.display());
+        }
+        Ok(true)
+    }
+}
+
+/// 校验附加更新包的清单（同目录文件或内嵌），未找到清单时按实际文件生成安装记录
+fn verify_extra(
+    archive_path: &Path,
+    archive_signature: SignatureStatus,
+    dir: &Path,
+    options: &UpdateOptions,
+    verbose: bool,
+) -> Result<PackageManifest, Box<dyn Error>> {
+    let Some((manifest, source)) = locate_manifest(
+        None,
+        archive_path,
+        archive_signature,
+        dir,
+        &options.signature,
+        verbose,
+    )?
+    else {
+        if options.require_manifest {
+            return Err(format!(
+                "附加更新包未找到更新清单，已要求必须校验清单 (--require-manifest): {}",
+                archive_path.display()
+            )
+            .into());
+        }
+        println!(
+            "⚠️  附加更新包未找到更新清单，跳过文件校验: {}",
+            archive_path.display()
+        );
+        return Ok(PackageManifest::from_directory(dir)?);
+    };
+
+    if manifest.delta.is_some() {
+        return Err(format!("附加更新包不支持差分包: {}", archive_path.display()).into());
+    }
+    verify_directory(&manifest, dir)?;
+    println!(
+        "✅ 附加更新包清单校验通过: {} 个文件 ({})",
+        manifest.files.len(),
+        source
+    );
+    let mut record = manifest.to_installed_record();
+    record.settings_migrations = manifest.settings_migrations;
+    Ok(record)
+}
+
+fn describe_package(path: &Path, version: Option<&str>) -> String {
+    format!("{} (版本: {})", path.display(), version.unwrap_or("未知"))
+}
+
+/// 暂存目录中的条目（相对路径）
+struct StagedEntry {
+    relative: PathBuf,
+    is_dir: bool,
+}
+
+/// 深度优先收集暂存目录中的所有条目，目录先于其内容
+fn collect_entries(staging_dir: &Path) -> io::Result<Vec<StagedEntry>> {
+    fn walk(root: &Path, dir: &Path, entries: &mut Vec<StagedEntry>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+
+            if entry.file_type()?.is_dir() {
+                entries.push(StagedEntry {
+                    relative,
+                    is_dir: true,
+                });
+                walk(root, &path, entries)?;
+            } else {
+                entries.push(StagedEntry {
+                    relative,
+                    is_dir: false,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    let mut entries = Vec::new();
+    walk(staging_dir, staging_dir, &mut entries)?;
+    Ok(entries)
+}
+
+/// 重命名文件，占用时重试；源文件仍被占用（例如正被杀毒软件扫描）时改为复制
+fn move_file(policy: RetryPolicy, source: &Path, target: &Path) -> io::Result<()> {
+    match retry_io(policy, || fs::rename(source, target)) {
+        Err(e) if is_locked(&e) => {
+            if let Err(copy_error) = fs::copy(source, target) {
+                let _ = fs::remove_file(target);
+                return Err(copy_error);
+            }
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn remove_path(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// 在 IO 错误中附加出错的路径，便于定位
+fn with_path(error: io::Error, path: &Path) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path.display(), error))
+}
+
+/// 在程序目录中创建并删除探测文件，确认当前权限可以写入
+fn ensure_writable(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let probe = dir.join(format!(".update-probe-{}", std::process::id()));
+    fs::write(&probe, b"")
+        .and_then(|()| fs::remove_file(&probe))
+        .map_err(|e| format!("程序目录不可写: {} ({})", dir.display(), e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 程序目录与以目录形式提供的更新包
+    struct Fixture {
+        _temp: tempfile::TempDir,
+        install_dir: PathBuf,
+        package: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(installed: &[(&str, &str)], package: &[(&str, &str)]) -> Self {
+            let temp = tempfile::tempdir().unwrap();
+            let install_dir = temp.path().join("app");
+            let package_dir = temp.path().join("package");
+            write_files(&install_dir, installed);
+            write_files(&package_dir, package);
+            Self {
+                install_dir,
+                package: package_dir,
+                _temp: temp,
+            }
+        }
+
+        fn read(&self, relative: &str) -> Option<String> {
+            fs::read_to_string(self.install_dir.join(relative)).ok()
+        }
+
+        fn journal(&self) -> PathBuf {
+            self.install_dir.join(WORK_DIR_NAME).join(JOURNAL_FILE_NAME)
+        }
+    }
+
+    /// 程序目录中除工作目录外的全部文件及其内容
+    fn snapshot(dir: &Path) -> BTreeMap<String, Vec<u8>> {
+        fn walk(root: &Path, dir: &Path, files: &mut BTreeMap<String, Vec<u8>>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                let relative = to_manifest_path(path.strip_prefix(root).unwrap());
+                if relative == WORK_DIR_NAME {
+                    continue;
+                }
+                if path.is_dir() {
+                    files.insert(format!("{}/", relative), Vec::new());
+                    walk(root, &path, files);
+                } else {
+                    files.insert(relative, fs::read(&path).unwrap());
+                }
+            }
+        }
+        let mut files = BTreeMap::new();
+        walk(dir, dir, &mut files);
+        files
+    }
+
+    /// 以程序目录当前内容写入安装记录，模拟由上一版本安装
+    fn write_record(dir: &Path) {
+        PackageManifest::from_directory(dir)
+            .unwrap()
+            .write(&dir.join(INSTALLED_MANIFEST_NAME))
+            .unwrap();
+    }
+
+    fn write_files(dir: &Path, files: &[(&str, &str)]) {
+        fs::create_dir_all(dir).unwrap();
+        for (relative, content) in files {
+            let path = dir.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    #[test]
+    fn journal_is_created_only_when_installing() {
+        let fixture = Fixture::new(&[("a.txt", "old")], &[("a.txt", "new")]);
+        let options = UpdateOptions::default();
+        let mut transaction = UpdateTransaction::new(&fixture.install_dir, false);
+
+        let staged = transaction.stage(&fixture.package, &options).unwrap();
+        // 暂存期间主程序仍在运行，不能让它看到未完成更新的日志
+        assert!(!fixture.journal().exists());
+        assert!(
+            UpdateTransaction::load_unfinished(&fixture.install_dir, false)
+                .unwrap()
+                .is_none()
+        );
+
+        transaction.install(staged, &options).unwrap();
+        assert!(fixture.journal().is_file());
+        transaction.commit();
+        assert!(!fixture.journal().exists());
+        assert_eq!(fixture.read("a.txt").as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn rollback_restores_the_original_tree() {
+        let fixture = Fixture::new(
+            &[("a.txt", "old"), ("sub/b.txt", "old")],
+            &[("a.txt", "new"), ("c.txt", "new"), ("sub/d/e.txt", "new")],
+        );
+        let before = snapshot(&fixture.install_dir);
+        let options = UpdateOptions::default();
+        let mut transaction = UpdateTransaction::new(&fixture.install_dir, false);
+
+        let staged = transaction.stage(&fixture.package, &options).unwrap();
+        transaction.install(staged, &options).unwrap();
+        assert_eq!(fixture.read("sub/d/e.txt").as_deref(), Some("new"));
+
+        transaction.rollback().unwrap();
+        assert_eq!(snapshot(&fixture.install_dir), before);
+        assert!(!fixture.install_dir.join(WORK_DIR_NAME).exists());
+    }
+
+    /// 上次遗留的延迟替换列表无法读取时，替换阶段在所有文件移入程序目录之后失败
+    fn failing_install() -> (Fixture, BTreeMap<String, Vec<u8>>, UpdateTransaction) {
+        let fixture = Fixture::new(
+            &[("a.txt", "old"), ("stale/x.txt", "old")],
+            &[("a.txt", "new"), ("stale", "file now")],
+        );
+        let list = fixture
+            .install_dir
+            .join(PENDING_DIR_NAME)
+            .join(PENDING_LIST_NAME);
+        fs::create_dir_all(list.parent().unwrap()).unwrap();
+        fs::write(&list, [0xff, 0xfe, 0x00]).unwrap();
+        let before = snapshot(&fixture.install_dir);
+
+        let options = UpdateOptions::default();
+        let mut transaction = UpdateTransaction::new(&fixture.install_dir, false);
+        let staged = transaction.stage(&fixture.package, &options).unwrap();
+        let error = transaction.install(staged, &options).unwrap_err();
+        assert_eq!(error.phase, UpdatePhase::Swap);
+        assert_eq!(fixture.read("stale").as_deref(), Some("file now"));
+        (fixture, before, transaction)
+    }
+
+    #[test]
+    fn failure_during_swap_is_rolled_back() {
+        let (fixture, before, transaction) = failing_install();
+
+        transaction.rollback().unwrap();
+        assert_eq!(snapshot(&fixture.install_dir), before);
+    }
+
+    #[test]
+    fn interrupted_install_is_rolled_back_from_a_partial_journal() {
+        let (fixture, before, transaction) = failing_install();
+        // 模拟进程在写入下一条日志时被终止
+        drop(transaction);
+        let mut journal = fs::OpenOptions::new()
+            .append(true)
+            .open(fixture.journal())
+            .unwrap();
+        io::Write::write_all(&mut journal, br#"{"type":"step","oper"#).unwrap();
+
+        let (transaction, summary) =
+            UpdateTransaction::load_unfinished(&fixture.install_dir, false)
+                .unwrap()
+                .unwrap();
+        assert_eq!(summary.phase, UpdatePhase::Swap);
+        assert!(summary.steps > 0);
+        transaction.rollback().unwrap();
+        assert_eq!(snapshot(&fixture.install_dir), before);
+        assert!(
+            UpdateTransaction::load_unfinished(&fixture.install_dir, false)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn interrupted_swap_can_be_finished() {
+        let fixture = Fixture::new(&[("a.txt", "old")], &[("a.txt", "new"), ("b.txt", "new")]);
+        let options = UpdateOptions::default();
+        let mut transaction = UpdateTransaction::new(&fixture.install_dir, false);
+        let staged = transaction.stage(&fixture.package, &options).unwrap();
+        transaction.install(staged, &options).unwrap();
+        drop(transaction);
+        // 模拟 b.txt 尚未移入程序目录时中断
+        let staging = fixture
+            .install_dir
+            .join(WORK_DIR_NAME)
+            .join(STAGING_DIR_NAME);
+        fs::rename(fixture.install_dir.join("b.txt"), staging.join("b.txt")).unwrap();
+
+        let (mut transaction, _) = UpdateTransaction::load_unfinished(&fixture.install_dir, false)
+            .unwrap()
+            .unwrap();
+        transaction.finish().unwrap();
+        transaction.commit();
+        assert_eq!(fixture.read("a.txt").as_deref(), Some("new"));
+        assert_eq!(fixture.read("b.txt").as_deref(), Some("new"));
+        assert!(!fixture.install_dir.join(WORK_DIR_NAME).exists());
+    }
+
+    #[test]
+    fn unchanged_files_are_not_replaced() {
+        let fixture = Fixture::new(
+            &[("same.txt", "same"), ("a.txt", "old")],
+            &[("same.txt", "same"), ("a.txt", "new")],
+        );
+        let options = UpdateOptions::default();
+        let mut transaction = UpdateTransaction::new(&fixture.install_dir, false);
+        let staged = transaction.stage(&fixture.package, &options).unwrap();
+        transaction.install(staged, &options).unwrap();
+
+        // 内容未变的文件既不备份也不记录为已安装
+        let backup = fixture
+            .install_dir
+            .join(WORK_DIR_NAME)
+            .join(BACKUP_DIR_NAME);
+        assert!(!backup.join("same.txt").exists());
+        assert!(backup.join("a.txt").is_file());
+        let report = transaction.commit();
+        assert_eq!(report.unchanged, 1);
+        // a.txt 与安装记录
+        assert_eq!(report.written, 2);
+        assert_eq!(fixture.read("same.txt").as_deref(), Some("same"));
+    }
+
+    #[test]
+    fn clean_mode_backs_up_everything_not_preserved() {
+        let fixture = Fixture::new(
+            &[
+                ("a.txt", "old"),
+                ("same.txt", "same"),
+                ("stale.txt", "old"),
+                ("old/x.txt", "old"),
+                ("Logs/today.log", "log"),
+                ("user/keep.txt", "mine"),
+                ("user/drop.txt", "old"),
+            ],
+            &[("a.txt", "new"), ("same.txt", "same")],
+        );
+        let before = snapshot(&fixture.install_dir);
+        let options = UpdateOptions {
+            clean: true,
+            preserve: vec!["user/keep.txt".to_string()],
+            ..Default::default()
+        };
+        let mut transaction = UpdateTransaction::new(&fixture.install_dir, false);
+        let staged = transaction.stage(&fixture.package, &options).unwrap();
+        transaction.install(staged, &options).unwrap();
+
+        let after = snapshot(&fixture.install_dir);
+        let files: Vec<&str> = after
+            .keys()
+            .map(String::as_str)
+            .filter(|path| !path.ends_with('/'))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                INSTALLED_MANIFEST_NAME,
+                "Logs/today.log",
+                "a.txt",
+                "same.txt",
+                "user/keep.txt"
+            ]
+        );
+
+        transaction.rollback().unwrap();
+        assert_eq!(snapshot(&fixture.install_dir), before);
+    }
+
+    #[test]
+    fn stale_files_of_the_previous_version_are_pruned() {
+        let fixture = Fixture::new(
+            &[
+                ("a.txt", "old"),
+                ("stale.txt", "old"),
+                ("gone/x.txt", "old"),
+                ("edited.txt", "old"),
+            ],
+            &[("a.txt", "new")],
+        );
+        write_record(&fixture.install_dir);
+        write_files(
+            &fixture.install_dir,
+            &[("edited.txt", "changed by user"), ("user.txt", "mine")],
+        );
+
+        let options = UpdateOptions::default();
+        let mut transaction = UpdateTransaction::new(&fixture.install_dir, false);
+        let staged = transaction.stage(&fixture.package, &options).unwrap();
+        transaction.install(staged, &options).unwrap();
+        let mut report = transaction.commit();
+
+        report.removed.sort();
+        assert_eq!(report.removed, ["gone/x.txt", "stale.txt"]);
+        assert_eq!(report.kept_modified, ["edited.txt"]);
+        assert_eq!(report.unknown, ["user.txt"]);
+        assert!(!fixture.install_dir.join("gone").exists());
+        assert_eq!(
+            fixture.read("edited.txt").as_deref(),
+            Some("changed by user")
+        );
+        assert_eq!(fixture.read("user.txt").as_deref(), Some("mine"));
+    }
+
+    #[test]
+    fn verified_copy_rejects_unverified_downloads() {
+        let fixture = Fixture::new(&[], &[]);
+        let archive = fixture.package.join("update.zip");
+        fs::write(&archive, b"package").unwrap();
+        let signature = default_signature_path(&archive);
+        let mut options = UpdateOptions {
+            unverified_downloads: vec![archive.clone()],
+            ..Default::default()
+        };
+        let transaction = UpdateTransaction::new(&fixture.install_dir, false);
+        transaction.prepare().unwrap();
+
+        let error = transaction
+            .verified_copy(&archive, &signature, 0, &options)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("--allow-unverified"), "{}", error);
+
+        options.allow_unverified = true;
+        let (copy, status) = transaction
+            .verified_copy(&archive, &signature, 0, &options)
+            .unwrap();
+        assert_eq!(status, SignatureStatus::Skipped);
+        assert!(copy.starts_with(fixture.install_dir.join(WORK_DIR_NAME)));
+        assert!(copy.ends_with("0-update.zip"));
+        // 之后替换原文件不影响已复制的副本
+        fs::write(&archive, b"replaced").unwrap();
+        assert_eq!(fs::read(&copy).unwrap(), b"package");
+    }
+}
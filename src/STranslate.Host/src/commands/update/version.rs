@@ -0,0 +1,244 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::manifest::PackageManifest;
+
+/// 主程序文件名，未提供版本标记时从其版本资源中读取版本
+pub const APP_EXE_NAME: &str = "STranslate.exe";
+
+/// `VS_FIXEDFILEINFO.dwSignature`
+const FIXED_FILE_INFO_SIGNATURE: [u8; 4] = 0xFEEF04BDu32.to_le_bytes();
+
+/// 版本号，例如 `2.0.1`、`2.0.1.0`、`2.1.0-beta.1`。
+///
+/// 数字部分逐段比较，缺少的段视为 0；带预发布标签的版本低于对应的正式版本，
+/// `+` 之后的构建信息不参与比较。
+#[derive(Clone, Debug)]
+pub struct Version {
+    text: String,
+    numbers: Vec<u64>,
+    pre_release: Option<String>,
+}
+
+impl Version {
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().trim_start_matches(['v', 'V']);
+        let core = text.split('+').next()?;
+        let (numbers, pre_release) = match core.split_once('-') {
+            Some((numbers, pre)) => (numbers, Some(pre.to_string())),
+            None => (core, None),
+        };
+
+        let numbers = numbers
+            .split('.')
+            .map(|part| part.parse().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        if numbers.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            text: text.to_string(),
+            numbers,
+            pre_release,
+        })
+    }
+
+    fn number(&self, index: usize) -> u64 {
+        self.numbers.get(index).copied().unwrap_or(0)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.numbers.len().max(other.numbers.len());
+        (0..len)
+            .map(|i| self.number(i).cmp(&other.number(i)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| match (&self.pre_release, &other.pre_release) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => compare_pre_release(a, b),
+            })
+    }
+}
+
+/// 预发布标签按 `.` 分段比较，纯数字段按数值比较且低于非数字段
+fn compare_pre_release(a: &str, b: &str) -> Ordering {
+    let mut left = a.split('.');
+    let mut right = b.split('.');
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => x.cmp(y),
+                };
+                if ordering.is_ne() {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+/// 当前安装的版本，优先取自安装记录，其次取自 `STranslate.exe` 的版本资源
+pub fn installed_version(install_dir: &Path) -> io::Result<Option<Version>> {
+    let recorded = PackageManifest::load_installed(install_dir)
+        .ok()
+        .flatten()
+        .and_then(|record| record.version)
+        .and_then(|text| Version::parse(&text));
+    match recorded {
+        Some(version) => Ok(Some(version)),
+        None => read_exe_version(&install_dir.join(APP_EXE_NAME)),
+    }
+}
+
+/// 从可执行文件的版本资源（`VS_FIXEDFILEINFO`）中读取产品版本
+pub fn read_exe_version(path: &Path) -> io::Result<Option<Version>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let content = fs::read(path)?;
+
+    let found = content
+        .windows(FIXED_FILE_INFO_SIGNATURE.len())
+        .enumerate()
+        .filter(|(_, window)| *window == FIXED_FILE_INFO_SIGNATURE)
+        .find_map(|(offset, _)| {
+            // dwSignature, dwStrucVersion, dwFileVersionMS/LS, dwProductVersionMS/LS
+            let field = |index: usize| {
+                let start = offset + index * 4;
+                content
+                    .get(start..start + 4)
+                    .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            };
+            if field(1)? != 0x0001_0000 {
+                return None;
+            }
+            Some((field(4)?, field(5)?))
+        });
+
+    Ok(found.and_then(|(ms, ls)| {
+        Version::parse(&format!(
+            "{}.{}.{}.{}",
+            ms >> 16,
+            ms & 0xFFFF,
+            ls >> 16,
+            ls & 0xFFFF
+        ))
+    }))
+}
+
+/// 拒绝降级或同版本重新安装
+#[derive(Debug)]
+pub struct VersionError {
+    pub installed: Version,
+    pub package: Version,
+}
+
+impl fmt::Display for VersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.package < self.installed {
+            write!(
+                f,
+                "更新包版本 {} 低于已安装版本 {}，拒绝降级（使用 --allow-downgrade 强制降级）",
+                self.package, self.installed
+            )
+        } else {
+            write!(
+                f,
+                "更新包版本 {} 与已安装版本相同，拒绝重新安装（使用 --reinstall 强制重新安装）",
+                self.package
+            )
+        }
+    }
+}
+
+impl Error for VersionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(text: &str) -> Version {
+        Version::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(v("v2.0.1").to_string(), "2.0.1");
+        assert_eq!(v(" 2.1.0-beta.1 ").to_string(), "2.1.0-beta.1");
+        assert!(Version::parse("").is_none());
+        assert!(Version::parse("2.x").is_none());
+        assert!(Version::parse("garbage").is_none());
+    }
+
+    #[test]
+    fn missing_segments_are_zero() {
+        assert_eq!(v("2.0"), v("2.0.0.0"));
+        assert!(v("2.0.1") > v("2.0.0.9"));
+        assert!(v("2.10.0") > v("2.9.0"));
+    }
+
+    #[test]
+    fn pre_release_is_lower_than_release() {
+        assert!(v("2.1.0-beta.1") < v("2.1.0"));
+        assert!(v("2.1.0-beta.1") > v("2.0.9"));
+        assert!(v("2.1.0-beta.2") < v("2.1.0-beta.10"));
+        assert!(v("2.1.0-alpha") < v("2.1.0-beta"));
+        assert!(v("2.1.0-beta") < v("2.1.0-beta.1"));
+        assert!(v("2.1.0-1") < v("2.1.0-alpha"));
+    }
+
+    #[test]
+    fn build_metadata_is_ignored() {
+        assert_eq!(v("2.1.0+20240101"), v("2.1.0+abc"));
+        assert_eq!(v("2.1.0+abc"), v("2.1.0"));
+    }
+
+    #[test]
+    fn version_error_describes_downgrade_and_reinstall() {
+        let downgrade = VersionError {
+            installed: v("2.1.0"),
+            package: v("2.0.0"),
+        };
+        assert!(downgrade.to_string().contains("--allow-downgrade"));
+        let reinstall = VersionError {
+            installed: v("2.1.0"),
+            package: v("2.1.0"),
+        };
+        assert!(reinstall.to_string().contains("--reinstall"));
+    }
+}
@@ -0,0 +1,211 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 目标卷剩余空间不足
+#[derive(Debug)]
+pub struct InsufficientSpaceError {
+    pub target: PathBuf,
+    /// 写入后新增占用的字节数（已扣除会被删除的文件）
+    pub required: u64,
+    pub available: u64,
+}
+
+impl fmt::Display for InsufficientSpaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "磁盘空间不足: {} 所在磁盘需要 {}，可用 {}，还差 {}",
+            self.target.display(),
+            format_size(self.required),
+            format_size(self.available),
+            format_size(self.required - self.available)
+        )
+    }
+}
+
+impl Error for InsufficientSpaceError {}
+
+/// 写入前检查目标所在卷的剩余空间。
+///
+/// `incoming` 为将要写入的总字节数，`reclaimed` 为写入前会被删除的文件大小。
+pub fn ensure_space(
+    target: &Path,
+    incoming: u64,
+    reclaimed: u64,
+    verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    let available = available_space(target)
+        .map_err(|e| format!("无法获取磁盘剩余空间: {} ({})", target.display(), e))?;
+
+    if verbose {
+        println!(
+            "💽 磁盘空间预检: 写入 {}，释放 {}，可用 {}",
+            format_size(incoming),
+            format_size(reclaimed),
+            format_size(available)
+        );
+    }
+
+    check_space(target, incoming, reclaimed, available)?;
+    Ok(())
+}
+
+/// 扣除会被删除的文件后，新增占用不能超过可用空间
+fn check_space(
+    target: &Path,
+    incoming: u64,
+    reclaimed: u64,
+    available: u64,
+) -> Result<(), InsufficientSpaceError> {
+    let required = incoming.saturating_sub(reclaimed);
+    if required > available {
+        return Err(InsufficientSpaceError {
+            target: target.to_path_buf(),
+            required,
+            available,
+        });
+    }
+    Ok(())
+}
+
+/// 统计目录（或文件）占用的字节数，不存在时为 0，不跟随符号链接
+pub fn path_size(path: &Path) -> io::Result<u64> {
+    let metadata = match path.symlink_metadata() {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    if !metadata.is_dir() {
+        return Ok(if metadata.is_file() {
+            metadata.len()
+        } else {
+            0
+        });
+    }
+
+    let mut total = 0u64;
+    for entry in path.read_dir()? {
+        total = total.saturating_add(path_size(&entry?.path())?);
+    }
+    Ok(total)
+}
+
+/// 以 B/KB/MB/GB 显示字节数
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// 查询路径所在卷对当前用户可用的剩余空间；路径不存在时使用最近的已存在上级目录
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    let absolute = std::path::absolute(path)?;
+    let existing = absolute
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "找不到已存在的上级目录"))?;
+    query_available(existing)
+}
+
+#[cfg(windows)]
+fn query_available(path: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::shared::ntdef::ULARGE_INTEGER;
+    use winapi::um::fileapi::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available: ULARGE_INTEGER = unsafe { std::mem::zeroed() };
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { *available.QuadPart() })
+}
+
+#[cfg(unix)]
+fn query_available(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Ok((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reclaimed_space_offsets_incoming_bytes() {
+        let target = Path::new("app");
+        assert!(check_space(target, 100, 40, 60).is_ok());
+
+        let error = check_space(target, 100, 40, 59).unwrap_err();
+        assert_eq!((error.required, error.available), (60, 59));
+        assert!(error.to_string().ends_with("还差 1 B"), "{}", error);
+    }
+
+    #[test]
+    fn reclaiming_more_than_incoming_needs_no_space() {
+        assert!(check_space(Path::new("app"), 10, 100, 0).is_ok());
+        assert!(check_space(Path::new("app"), 0, 0, 0).is_ok());
+    }
+
+    #[test]
+    fn ensure_space_checks_the_volume_of_a_missing_target() {
+        let temp = tempfile::tempdir().unwrap();
+        // 目标尚不存在时按最近的已存在上级目录查询
+        let target = temp.path().join("not/created/yet");
+        assert!(ensure_space(&target, 0, 0, false).is_ok());
+
+        let error = ensure_space(&target, u64::MAX, 0, false).unwrap_err();
+        let error = error.downcast_ref::<InsufficientSpaceError>().unwrap();
+        assert_eq!(error.required, u64::MAX);
+        assert_eq!(error.target, target);
+    }
+
+    #[test]
+    fn path_size_sums_nested_files() {
+        let temp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(temp.path().join("a/b")).unwrap();
+        fs::write(temp.path().join("one"), [0u8; 10]).unwrap();
+        fs::write(temp.path().join("a/b/two"), [0u8; 32]).unwrap();
+
+        assert_eq!(path_size(temp.path()).unwrap(), 42);
+        assert_eq!(path_size(&temp.path().join("one")).unwrap(), 10);
+        assert_eq!(path_size(&temp.path().join("missing")).unwrap(), 0);
+    }
+
+    #[test]
+    fn sizes_are_formatted_with_binary_units() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KB");
+        assert_eq!(format_size(1536 * 1024), "1.5 MB");
+        assert_eq!(format_size(u64::MAX), "16777216.0 TB");
+    }
+}
@@ -1,10 +1,17 @@
+mod archive;
 mod commands;
+mod disk;
+mod process;
+mod proxy;
 
 use clap::{Arg, ArgAction, Command};
 
+use crate::archive::extract::StripComponents;
 use crate::commands::{
-    BackupMode, StartMode, TaskAction, handle_backup_command, handle_start_command,
-    handle_task_command, handle_update_command,
+    BackupMode, Channel, LaunchPrivilege, RecoverMode, SKIP_UAC_TASK_NAME, StartMode, TaskAction,
+    finish_host_update_on_launch, handle_apply_pending_command, handle_backup_command,
+    handle_check_update_command, handle_recover_command, handle_start_command, handle_task_command,
+    handle_update_command, update_exit_code,
 };
 
 fn main() {
@@ -19,9 +26,10 @@ fn main() {
                     Arg::new("archive")
                         .short('a')
                         .long("archive")
-                        .value_name("PATH")
-                        .help("缓存的压缩包路径")
-                        .required(true),
+                        .value_name("PATH|URL")
+                        .help("缓存的压缩包路径、已解压的目录或 HTTP(S) 下载地址（按文件头识别 zip、tar.gz、tar.zst、7z）；可重复指定，后续更新包（例如插件包）与第一个在同一事务中安装")
+                        .required(true)
+                        .action(ArgAction::Append),
                 )
                 .arg(
                     Arg::new("wait-time")
@@ -37,21 +45,272 @@ fn main() {
                         .short('c')
                         .long("clean")
                         .action(ArgAction::SetTrue)
-                        .help("是否清理必要目录（保留 log、portable_config、tmp 目录）"),
+                        .help("是否清理必要目录（默认保留 Logs、PortableConfig、tmp 目录）"),
+                )
+                .arg(
+                    Arg::new("preserve")
+                        .long("preserve")
+                        .value_name("PATTERN")
+                        .help("清理时额外保留的路径模式（相对程序目录，大小写不敏感，支持 * 与 **），可重复指定")
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("data-dir")
+                        .long("data-dir")
+                        .value_name("PATH")
+                        .help("当前使用的数据目录，清理时始终保留（默认检测 PortableConfig）"),
+                )
+                .arg(
+                    Arg::new("plugins-dir")
+                        .long("plugins-dir")
+                        .value_name("PATH")
+                        .help("用户插件目录，更新包声明了插件兼容性要求时检查其中的插件（默认 <数据目录>/Plugins）"),
+                )
+                .arg(
+                    Arg::new("settings-dir")
+                        .long("settings-dir")
+                        .value_name("PATH")
+                        .help("用户设置目录，更新包声明了设置迁移时在其中执行（默认 <数据目录>/Settings）"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("试运行：只校验更新包并报告插件兼容性与将执行的设置迁移，不关闭程序、不修改任何文件"),
+                )
+                .arg(
+                    Arg::new("disable-incompatible-plugins")
+                        .long("disable-incompatible-plugins")
+                        .action(ArgAction::SetTrue)
+                        .help("更新完成后为不兼容的用户插件创建 NeedDisable.txt 标记，主程序启动时跳过这些插件但保留其文件；删除标记即可重新启用"),
+                )
+                .arg(
+                    Arg::new("manifest")
+                        .short('m')
+                        .long("manifest")
+                        .value_name("FILE")
+                        .help("外部更新清单路径（默认 <压缩包>.manifest.json）；未签名时优先使用包内 update-manifest.json"),
+                )
+                .arg(
+                    Arg::new("signature")
+                        .long("signature")
+                        .value_name("FILE")
+                        .help("minisign 签名文件路径（默认 <压缩包>.minisig）"),
+                )
+                .arg(
+                    Arg::new("public-key")
+                        .long("public-key")
+                        .value_name("FILE")
+                        .help("受信任的 minisign 公钥文件"),
+                )
+                .arg(
+                    Arg::new("require-signature")
+                        .long("require-signature")
+                        .action(ArgAction::SetTrue)
+                        .help("更新包与外部清单都必须通过签名校验才允许更新（校验失败退出码为 3）"),
+                )
+                .arg(
+                    Arg::new("require-manifest")
+                        .long("require-manifest")
+                        .action(ArgAction::SetTrue)
+                        .help("找不到更新清单时拒绝更新"),
+                )
+                .arg(
+                    Arg::new("fallback-archive")
+                        .long("fallback-archive")
+                        .value_name("PATH|URL")
+                        .help("差分包的基础文件不匹配时改用的完整更新包或其下载地址（未指定时退出码为 5）"),
+                )
+                .arg(
+                    Arg::new("install-dir")
+                        .long("install-dir")
+                        .visible_alias("target")
+                        .value_name("PATH")
+                        .help("程序目录（默认：本地压缩包为其上两级目录，下载地址为宿主程序所在目录）"),
+                )
+                .arg(
+                    Arg::new("cache-dir")
+                        .long("cache-dir")
+                        .value_name("PATH")
+                        .help("下载缓存目录，中断后再次执行时从此处续传（默认 <程序目录>/.update-cache）"),
+                )
+                .arg(
+                    Arg::new("sha256")
+                        .long("sha256")
+                        .value_name("HEX")
+                        .help("下载内容的 SHA-256 校验值（默认读取 <下载地址>.sha256）"),
+                )
+                .arg(
+                    Arg::new("fallback-sha256")
+                        .long("fallback-sha256")
+                        .value_name("HEX")
+                        .help("完整更新包下载内容的 SHA-256 校验值（默认读取 <下载地址>.sha256）"),
+                )
+                .arg(
+                    Arg::new("allow-unverified")
+                        .long("allow-unverified")
+                        .action(ArgAction::SetTrue)
+                        .help("允许安装既未通过 --sha256 校验也未通过签名校验的下载内容（<下载地址>.sha256 只用于发现传输损坏）"),
+                )
+                .arg(
+                    Arg::new("download-timeout")
+                        .long("download-timeout")
+                        .value_name("SECONDS")
+                        .help("下载时的连接与读取超时（秒）")
+                        .default_value("30")
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("download-retries")
+                        .long("download-retries")
+                        .value_name("N")
+                        .help("下载失败后的重试次数")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(u32)),
+                )
+                .args(proxy_args())
+                .arg(
+                    Arg::new("strip-components")
+                        .long("strip-components")
+                        .value_name("N|auto")
+                        .help("主更新包解压时去除的顶层目录层数；auto 表示所有内容位于同一顶层目录时自动去除（附加更新包按原样解压）")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(StripComponents)),
+                )
+                .arg(
+                    Arg::new("allow-downgrade")
+                        .long("allow-downgrade")
+                        .action(ArgAction::SetTrue)
+                        .help("允许安装低于当前版本的更新包（默认拒绝，退出码为 4）"),
+                )
+                .arg(
+                    Arg::new("reinstall")
+                        .long("reinstall")
+                        .action(ArgAction::SetTrue)
+                        .help("允许重新安装与当前版本相同的更新包（默认拒绝，退出码为 4）"),
                 )
                 .arg(
                     Arg::new("process-name")
                         .short('p')
                         .long("process")
                         .value_name("NAME")
-                        .help("要关闭的进程名称"),
+                        .help("要关闭的进程名称（仅匹配当前会话中位于程序目录下的进程）"),
+                )
+                .arg(
+                    Arg::new("pid")
+                        .long("pid")
+                        .value_name("PID")
+                        .help("要关闭的进程 ID，可重复指定")
+                        .value_parser(clap::value_parser!(u32))
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("process-path")
+                        .long("process-path")
+                        .value_name("PATH")
+                        .help("要关闭的进程可执行文件完整路径，可重复指定")
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("shutdown-timeout")
+                        .long("shutdown-timeout")
+                        .value_name("SECONDS")
+                        .help("等待进程正常退出的超时时间（秒），超时后强制结束")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u64)),
                 )
                 .arg(
                     Arg::new("auto-start")
                         .short('s')
                         .long("auto-start")
                         .action(ArgAction::SetTrue)
-                        .help("更新完成后自动启动程序"),
+                        .help("更新完成后自动启动程序（更新失败时不启动）"),
+                )
+                .arg(
+                    Arg::new("launch-exe")
+                        .long("launch-exe")
+                        .value_name("PATH")
+                        .help("自动启动的程序，相对路径相对于程序目录")
+                        .default_value("STranslate.exe")
+                        .requires("auto-start"),
+                )
+                .arg(
+                    Arg::new("launch-arg")
+                        .long("launch-arg")
+                        .value_name("ARG")
+                        .help("自动启动时传递的参数，可重复指定")
+                        .allow_hyphen_values(true)
+                        .action(ArgAction::Append)
+                        .requires("auto-start"),
+                )
+                .arg(
+                    Arg::new("launch-dir")
+                        .long("launch-dir")
+                        .value_name("PATH")
+                        .help("自动启动时的工作目录（默认为程序目录）")
+                        .requires("auto-start"),
+                )
+                .arg(
+                    Arg::new("launch-privilege")
+                        .long("launch-privilege")
+                        .value_name("MODE")
+                        .help("自动启动使用的权限：same 与更新程序相同，unelevated 以普通用户权限，task 通过跳过 UAC 的任务计划")
+                        .value_parser(clap::value_parser!(LaunchPrivilege))
+                        .default_value("same")
+                        .requires("auto-start"),
+                )
+                .arg(
+                    Arg::new("launch-task")
+                        .long("launch-task")
+                        .value_name("NAME")
+                        .help("--launch-privilege task 使用的任务计划名称")
+                        .default_value(SKIP_UAC_TASK_NAME)
+                        .requires("auto-start"),
+                )
+                .arg(
+                    Arg::new("verbose")
+                        .short('v')
+                        .long("verbose")
+                        .action(ArgAction::SetTrue)
+                        .help("显示详细输出"),
+                ),
+        )
+        .subcommand(
+            Command::new("recover")
+                .about("继续完成或回滚上次中断的更新")
+                .arg(
+                    Arg::new("install-dir")
+                        .short('d')
+                        .long("install-dir")
+                        .value_name("PATH")
+                        .help("程序目录，默认为宿主程序所在目录"),
+                )
+                .arg(
+                    Arg::new("mode")
+                        .short('m')
+                        .long("mode")
+                        .value_name("MODE")
+                        .help("处理方式")
+                        .value_parser(clap::value_parser!(RecoverMode))
+                        .default_value("auto"),
+                )
+                .arg(
+                    Arg::new("verbose")
+                        .short('v')
+                        .long("verbose")
+                        .action(ArgAction::SetTrue)
+                        .help("显示详细输出"),
+                ),
+        )
+        .subcommand(
+            Command::new("apply-pending")
+                .about("应用上次更新因文件被占用而延迟的替换（主程序启动时调用）")
+                .arg(
+                    Arg::new("install-dir")
+                        .short('d')
+                        .long("install-dir")
+                        .value_name("PATH")
+                        .help("程序目录，默认为宿主程序所在目录"),
                 )
                 .arg(
                     Arg::new("verbose")
@@ -61,6 +320,63 @@ fn main() {
                         .help("显示详细输出"),
                 ),
         )
+        .subcommand(
+            Command::new("check-update")
+                .about("根据发布源检查是否有可用更新，以 JSON 输出结果")
+                .arg(
+                    Arg::new("feed")
+                        .short('f')
+                        .long("feed")
+                        .value_name("PATH|URL")
+                        .help("发布源 JSON 的本地路径或 HTTP(S) 地址")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("channel")
+                        .short('c')
+                        .long("channel")
+                        .value_name("CHANNEL")
+                        .help("更新渠道（beta 同时接收正式版）")
+                        .value_parser(clap::value_parser!(Channel))
+                        .default_value("stable"),
+                )
+                .arg(
+                    Arg::new("install-dir")
+                        .short('d')
+                        .long("install-dir")
+                        .value_name("PATH")
+                        .help("程序目录，用于读取已安装版本，默认为宿主程序所在目录"),
+                )
+                .arg(
+                    Arg::new("current-version")
+                        .long("current-version")
+                        .value_name("VERSION")
+                        .help("指定当前版本，不从程序目录读取"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("将结果写入文件而不是标准输出"),
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .value_name("SECONDS")
+                        .help("获取发布源时的连接与读取超时（秒）")
+                        .default_value("30")
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .args(proxy_args())
+                .arg(
+                    Arg::new("verbose")
+                        .short('v')
+                        .long("verbose")
+                        .action(ArgAction::SetTrue)
+                        .help("在标准错误中显示详细输出"),
+                ),
+        )
         .subcommand(
             Command::new("start")
                 .about("后台启动程序")
@@ -268,10 +584,31 @@ fn main() {
         )
         .get_matches();
 
+    // 上次更新未能替换正在运行的宿主程序时，在执行任何命令前完成替换
+    finish_host_update_on_launch();
+
     match matches.subcommand() {
         Some(("update", sub_matches)) => {
             if let Err(e) = handle_update_command(sub_matches) {
                 eprintln!("❌ 更新失败: {}", e);
+                std::process::exit(update_exit_code(e.as_ref()));
+            }
+        }
+        Some(("recover", sub_matches)) => {
+            if let Err(e) = handle_recover_command(sub_matches) {
+                eprintln!("❌ 恢复失败: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(("apply-pending", sub_matches)) => {
+            if let Err(e) = handle_apply_pending_command(sub_matches) {
+                eprintln!("❌ 应用延迟替换失败: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(("check-update", sub_matches)) => {
+            if let Err(e) = handle_check_update_command(sub_matches) {
+                eprintln!("❌ 检查更新失败: {}", e);
                 std::process::exit(1);
             }
         }
@@ -294,9 +631,30 @@ fn main() {
             }
         }
         _ => {
-            eprintln!("❌ 请指定命令: update、start、task 或 backup");
+            eprintln!(
+                "❌ 请指定命令: update、recover、apply-pending、check-update、start、task 或 backup"
+            );
             eprintln!("使用 --help 查看帮助信息");
             std::process::exit(1);
         }
     }
 }
+
+/// 代理选项，优先级：命令行 > 环境变量 > 主程序设置 > 系统代理
+fn proxy_args() -> [Arg; 3] {
+    [
+        Arg::new("proxy")
+            .long("proxy")
+            .value_name("URL")
+            .help("代理地址，支持 http:// 与 socks5://，可包含 user:password@（优先于环境变量 HTTP(S)_PROXY/ALL_PROXY、主程序设置与系统代理）")
+            .conflicts_with("no-proxy"),
+        Arg::new("no-proxy")
+            .long("no-proxy")
+            .action(ArgAction::SetTrue)
+            .help("忽略所有代理配置，直接连接"),
+        Arg::new("settings-file")
+            .long("settings-file")
+            .value_name("FILE")
+            .help("读取代理设置的主程序配置文件（默认为数据目录下的 Settings/Settings.json）"),
+    ]
+}
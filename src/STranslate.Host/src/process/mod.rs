@@ -0,0 +1,400 @@
+#[cfg(unix)]
+mod unix;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(unix)]
+pub use unix::{is_elevated, spawn_unelevated};
+#[cfg(windows)]
+pub use windows::{is_elevated, spawn_unelevated};
+
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 平台相关的进程操作实现
+pub struct SystemProcesses;
+
+/// 当前用户会话中的进程信息
+#[derive(Clone, Debug)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    /// 可执行文件完整路径，无权限查询时为 `None`
+    pub exe: Option<PathBuf>,
+}
+
+/// 进程匹配与控制的抽象，便于在 Windows 与 Unix 上共用关闭流程
+pub trait ProcessControl {
+    /// 列出当前用户会话中的进程
+    fn list(&self) -> io::Result<Vec<ProcessInfo>>;
+    /// 请求进程正常退出（Windows 设置主程序的退出事件或发送 WM_CLOSE，Unix 发送 SIGTERM）
+    fn request_exit(&self, pid: u32) -> io::Result<()>;
+    fn is_running(&self, pid: u32) -> bool;
+    /// 强制结束进程
+    fn kill(&self, pid: u32) -> io::Result<()>;
+}
+
+/// 要关闭的目标进程
+#[derive(Clone, Debug)]
+pub enum ProcessTarget {
+    Pid(u32),
+    /// 可执行文件的完整路径
+    ExePath(PathBuf),
+    /// 可执行文件名，可限定必须位于指定目录下
+    Name {
+        name: String,
+        within: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct ShutdownOptions {
+    /// 等待进程正常退出的超时时间
+    pub timeout: Duration,
+    /// 轮询间隔
+    pub poll_interval: Duration,
+    pub verbose: bool,
+}
+
+impl Default for ShutdownOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            poll_interval: Duration::from_millis(200),
+            verbose: false,
+        }
+    }
+}
+
+/// 关闭结果
+#[derive(Debug, Default)]
+pub struct ShutdownReport {
+    /// 正常退出的进程
+    pub graceful: Vec<u32>,
+    /// 超时后被强制结束的进程
+    pub forced: Vec<u32>,
+}
+
+/// 根据目标匹配当前会话中的进程（不包括自身）
+pub fn resolve_targets<C: ProcessControl>(
+    control: &C,
+    targets: &[ProcessTarget],
+) -> io::Result<Vec<ProcessInfo>> {
+    let own_pid = std::process::id();
+    let processes = control.list()?;
+    let mut matched: Vec<ProcessInfo> = Vec::new();
+
+    for target in targets {
+        for process in &processes {
+            if process.pid == own_pid || matched.iter().any(|p| p.pid == process.pid) {
+                continue;
+            }
+            if matches_target(process, target) {
+                matched.push(process.clone());
+            }
+        }
+
+        if let ProcessTarget::Pid(pid) = target
+            && !matched.iter().any(|p| p.pid == *pid)
+            && *pid != own_pid
+            && control.is_running(*pid)
+        {
+            matched.push(ProcessInfo {
+                pid: *pid,
+                exe: None,
+            });
+        }
+    }
+
+    Ok(matched)
+}
+
+fn matches_target(process: &ProcessInfo, target: &ProcessTarget) -> bool {
+    match target {
+        ProcessTarget::Pid(pid) => process.pid == *pid,
+        ProcessTarget::ExePath(path) => process
+            .exe
+            .as_deref()
+            .is_some_and(|exe| same_path(exe, path)),
+        ProcessTarget::Name { name, within } => {
+            let Some(exe) = process.exe.as_deref() else {
+                return false;
+            };
+            let name_matches = [exe.file_name(), exe.file_stem()]
+                .into_iter()
+                .flatten()
+                .any(|n| n.to_string_lossy().eq_ignore_ascii_case(name));
+            name_matches && within.as_deref().is_none_or(|dir| is_within(exe, dir))
+        }
+    }
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn is_within(path: &Path, dir: &Path) -> bool {
+    match (path.canonicalize(), dir.canonicalize()) {
+        (Ok(path), Ok(dir)) => path.starts_with(dir),
+        _ => false,
+    }
+}
+
+/// 先请求正常退出并轮询等待，超时后才强制结束
+pub fn shutdown<C: ProcessControl>(
+    control: &C,
+    processes: &[ProcessInfo],
+    options: &ShutdownOptions,
+) -> Result<ShutdownReport, Box<dyn Error>> {
+    let mut report = ShutdownReport::default();
+    if processes.is_empty() {
+        return Ok(report);
+    }
+
+    for process in processes {
+        if options.verbose {
+            match &process.exe {
+                Some(exe) => println!("📨 请求进程退出: {} ({})", process.pid, exe.display()),
+                None => println!("📨 请求进程退出: {}", process.pid),
+            }
+        }
+        if let Err(e) = control.request_exit(process.pid)
+            && options.verbose
+        {
+            println!("⚠️  无法发送退出请求: {} ({})", process.pid, e);
+        }
+    }
+
+    let pids: Vec<u32> = processes.iter().map(|p| p.pid).collect();
+    let remaining = wait_for_exit(control, &pids, options.timeout, options.poll_interval);
+    report.graceful = pids
+        .iter()
+        .copied()
+        .filter(|pid| !remaining.contains(pid))
+        .collect();
+
+    if remaining.is_empty() {
+        return Ok(report);
+    }
+
+    for pid in &remaining {
+        println!(
+            "⚠️  进程 {} 未在 {:?} 内退出，强制结束",
+            pid, options.timeout
+        );
+        control
+            .kill(*pid)
+            .map_err(|e| format!("强制结束进程 {} 失败: {}", pid, e))?;
+    }
+
+    let still_running = wait_for_exit(
+        control,
+        &remaining,
+        Duration::from_secs(5),
+        options.poll_interval,
+    );
+    if !still_running.is_empty() {
+        return Err(format!("以下进程仍未退出: {:?}", still_running).into());
+    }
+
+    report.forced = remaining;
+    Ok(report)
+}
+
+/// 轮询直到所有进程退出或超时，返回仍在运行的进程
+fn wait_for_exit<C: ProcessControl>(
+    control: &C,
+    pids: &[u32],
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Vec<u32> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let running: Vec<u32> = pids
+            .iter()
+            .copied()
+            .filter(|pid| control.is_running(*pid))
+            .collect();
+        if running.is_empty() || Instant::now() >= deadline {
+            return running;
+        }
+        thread::sleep(poll_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::BTreeSet;
+
+    /// 模拟的进程表：`obedient` 中的进程收到退出请求后退出，其余进程只能被强制结束
+    #[derive(Default)]
+    struct FakeProcesses {
+        processes: Vec<ProcessInfo>,
+        running: RefCell<BTreeSet<u32>>,
+        obedient: BTreeSet<u32>,
+        requested: RefCell<Vec<u32>>,
+        killed: RefCell<Vec<u32>>,
+    }
+
+    impl FakeProcesses {
+        fn new(processes: &[(u32, Option<&str>)]) -> Self {
+            let processes: Vec<ProcessInfo> = processes
+                .iter()
+                .map(|(pid, exe)| ProcessInfo {
+                    pid: *pid,
+                    exe: exe.map(PathBuf::from),
+                })
+                .collect();
+            let running = processes.iter().map(|p| p.pid).collect();
+            Self {
+                processes,
+                running: RefCell::new(running),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl ProcessControl for FakeProcesses {
+        fn list(&self) -> io::Result<Vec<ProcessInfo>> {
+            Ok(self.processes.clone())
+        }
+
+        fn request_exit(&self, pid: u32) -> io::Result<()> {
+            self.requested.borrow_mut().push(pid);
+            if self.obedient.contains(&pid) {
+                self.running.borrow_mut().remove(&pid);
+            }
+            Ok(())
+        }
+
+        fn is_running(&self, pid: u32) -> bool {
+            self.running.borrow().contains(&pid)
+        }
+
+        fn kill(&self, pid: u32) -> io::Result<()> {
+            self.killed.borrow_mut().push(pid);
+            self.running.borrow_mut().remove(&pid);
+            Ok(())
+        }
+    }
+
+    fn pids(processes: &[ProcessInfo]) -> Vec<u32> {
+        processes.iter().map(|p| p.pid).collect()
+    }
+
+    fn touch(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"").unwrap();
+    }
+
+    fn quick() -> ShutdownOptions {
+        ShutdownOptions {
+            timeout: Duration::from_millis(50),
+            poll_interval: Duration::from_millis(5),
+            verbose: false,
+        }
+    }
+
+    #[test]
+    fn resolve_targets_matches_each_kind_once_and_skips_itself() {
+        let own = std::process::id();
+        let control = FakeProcesses::new(&[
+            (own, Some("/opt/app/z_stranslate_host")),
+            (10, Some("/opt/app/STranslate.exe")),
+            (11, Some("/other/STranslate.exe")),
+            (12, Some("/opt/app/helper")),
+            (13, None),
+        ]);
+        control.running.borrow_mut().insert(42);
+
+        let targets = [
+            ProcessTarget::Name {
+                name: "stranslate".to_string(),
+                within: None,
+            },
+            ProcessTarget::ExePath(PathBuf::from("/opt/app/helper")),
+            ProcessTarget::Pid(10),
+            // 未出现在进程列表中但仍在运行
+            ProcessTarget::Pid(42),
+            ProcessTarget::Pid(99),
+            ProcessTarget::Pid(own),
+            ProcessTarget::ExePath(PathBuf::from("/opt/app/z_stranslate_host")),
+        ];
+        let matched = resolve_targets(&control, &targets).unwrap();
+        assert_eq!(pids(&matched), [10, 11, 12, 42]);
+        assert!(matched[3].exe.is_none());
+    }
+
+    #[test]
+    fn resolve_targets_limits_names_to_the_directory() {
+        let temp = tempfile::tempdir().unwrap();
+        let inside = temp.path().join("app");
+        let outside = temp.path().join("other");
+        for dir in [&inside, &outside] {
+            touch(&dir.join("STranslate.exe"));
+        }
+        let control = FakeProcesses::new(&[
+            (10, inside.join("STranslate.exe").to_str()),
+            (11, outside.join("STranslate.exe").to_str()),
+        ]);
+
+        let targets = [ProcessTarget::Name {
+            name: "STranslate.exe".to_string(),
+            within: Some(inside),
+        }];
+        assert_eq!(pids(&resolve_targets(&control, &targets).unwrap()), [10]);
+    }
+
+    #[test]
+    fn shutdown_kills_only_processes_that_ignore_the_request() {
+        let mut control = FakeProcesses::new(&[(10, None), (11, None)]);
+        control.obedient.insert(10);
+        let processes = control.list().unwrap();
+
+        let report = shutdown(&control, &processes, &quick()).unwrap();
+        assert_eq!(report.graceful, [10]);
+        assert_eq!(report.forced, [11]);
+        assert_eq!(*control.requested.borrow(), [10, 11]);
+        assert_eq!(*control.killed.borrow(), [11]);
+    }
+
+    #[test]
+    fn shutdown_without_processes_does_nothing() {
+        let control = FakeProcesses::default();
+        let report = shutdown(&control, &[], &quick()).unwrap();
+        assert!(report.graceful.is_empty() && report.forced.is_empty());
+        assert!(control.requested.borrow().is_empty());
+    }
+
+    #[test]
+    fn wait_for_exit_returns_processes_still_running_at_the_deadline() {
+        let control = FakeProcesses::new(&[(10, None), (11, None)]);
+        control.running.borrow_mut().remove(&10);
+
+        let started = Instant::now();
+        let remaining = wait_for_exit(
+            &control,
+            &[10, 11],
+            Duration::from_millis(30),
+            Duration::from_millis(5),
+        );
+        assert_eq!(remaining, [11]);
+        assert!(started.elapsed() >= Duration::from_millis(30));
+        assert!(
+            wait_for_exit(
+                &control,
+                &[10],
+                Duration::from_secs(5),
+                Duration::from_secs(1)
+            )
+            .is_empty()
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+
+use super::{ProcessControl, ProcessInfo, SystemProcesses};
+
+impl ProcessControl for SystemProcesses {
+    /// 通过 `/proc` 列出当前用户的进程；没有 `/proc` 的系统只支持按 PID 关闭
+    fn list(&self) -> io::Result<Vec<ProcessInfo>> {
+        let uid = unsafe { libc::geteuid() };
+        let mut processes = Vec::new();
+
+        let Ok(entries) = fs::read_dir("/proc") else {
+            return Ok(processes);
+        };
+
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+                continue;
+            };
+            let owned = entry.metadata().is_ok_and(|m| m.uid() == uid);
+            if !owned {
+                continue;
+            }
+            processes.push(ProcessInfo {
+                pid,
+                exe: fs::read_link(entry.path().join("exe")).ok(),
+            });
+        }
+
+        Ok(processes)
+    }
+
+    fn request_exit(&self, pid: u32) -> io::Result<()> {
+        send_signal(pid, libc::SIGTERM)
+    }
+
+    fn is_running(&self, pid: u32) -> bool {
+        if let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            // 僵尸进程已经退出，只是尚未被父进程回收
+            let state = stat
+                .rsplit(')')
+                .next()
+                .and_then(|s| s.trim().chars().next());
+            return state != Some('Z');
+        }
+        if unsafe { libc::kill(pid as libc::pid_t, 0) } == 0 {
+            return true;
+        }
+        // EPERM 说明进程存在，只是无权向其发送信号
+        io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+
+    fn kill(&self, pid: u32) -> io::Result<()> {
+        send_signal(pid, libc::SIGKILL)
+    }
+}
+
+fn send_signal(pid: u32, signal: libc::c_int) -> io::Result<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// 是否以 root 身份运行
+pub fn is_elevated() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// 通过 sudo 以 root 运行时，以调用 sudo 的用户身份启动程序
+pub fn spawn_unelevated(program: &Path, args: &[String], working_dir: &Path) -> io::Result<u32> {
+    let id = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .and_then(|value| value.parse().ok())
+    };
+    let (Some(uid), Some(gid)) = (id("SUDO_UID"), id("SUDO_GID")) else {
+        return Err(io::Error::other("无法确定普通用户身份（未通过 sudo 运行）"));
+    };
+
+    let child = Command::new(program)
+        .args(args)
+        .current_dir(working_dir)
+        .uid(uid)
+        .gid(gid)
+        .spawn()?;
+    Ok(child.id())
+}
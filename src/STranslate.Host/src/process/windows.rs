@@ -0,0 +1,363 @@
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::mem;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use winapi::shared::minwindef::{BOOL, DWORD, FALSE, LPARAM, MAX_PATH, TRUE};
+use winapi::shared::windef::HWND;
+use winapi::shared::winerror::{ERROR_INVALID_PARAMETER, WAIT_TIMEOUT};
+use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+use winapi::um::processthreadsapi::{
+    GetCurrentProcess, GetCurrentProcessId, OpenProcess, OpenProcessToken, PROCESS_INFORMATION,
+    ProcessIdToSessionId, STARTUPINFOW, TerminateProcess,
+};
+use winapi::um::securitybaseapi::{DuplicateTokenEx, GetTokenInformation};
+use winapi::um::synchapi::{OpenEventW, SetEvent, WaitForSingleObject};
+use winapi::um::tlhelp32::{
+    CreateToolhelp32Snapshot, PROCESSENTRY32W, Process32FirstW, Process32NextW, TH32CS_SNAPPROCESS,
+};
+use winapi::um::winbase::{CreateProcessWithTokenW, QueryFullProcessImageNameW};
+use winapi::um::winnt::{
+    EVENT_MODIFY_STATE, HANDLE, PROCESS_QUERY_INFORMATION, PROCESS_QUERY_LIMITED_INFORMATION,
+    PROCESS_TERMINATE, SYNCHRONIZE, SecurityImpersonation, TOKEN_ADJUST_DEFAULT,
+    TOKEN_ADJUST_SESSIONID, TOKEN_ASSIGN_PRIMARY, TOKEN_DUPLICATE, TOKEN_ELEVATION, TOKEN_QUERY,
+    TokenElevation, TokenPrimary,
+};
+use winapi::um::winuser::{
+    EnumWindows, GetShellWindow, GetWindowThreadProcessId, PostMessageW, WM_CLOSE,
+};
+
+use super::{ProcessControl, ProcessInfo, SystemProcesses};
+
+/// 主程序请求退出的命名事件前缀，后接进程 ID，与 `Constant.ExitEventPrefix` 保持一致
+const EXIT_EVENT_PREFIX: &str = r"Local\STranslate_Exit_";
+
+/// 进程句柄的 RAII 封装
+struct ProcessHandle(HANDLE);
+
+impl ProcessHandle {
+    fn open(pid: u32, access: DWORD) -> io::Result<Self> {
+        let handle = unsafe { OpenProcess(access, FALSE, pid) };
+        if handle.is_null() {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(Self(handle))
+        }
+    }
+}
+
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+impl ProcessControl for SystemProcesses {
+    /// 枚举与当前进程处于同一会话的进程，避免影响其他用户会话
+    fn list(&self) -> io::Result<Vec<ProcessInfo>> {
+        let own_session = session_id(unsafe { GetCurrentProcessId() });
+
+        let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) };
+        if snapshot == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut processes = Vec::new();
+        let mut entry: PROCESSENTRY32W = unsafe { mem::zeroed() };
+        entry.dwSize = mem::size_of::<PROCESSENTRY32W>() as DWORD;
+
+        let mut ok = unsafe { Process32FirstW(snapshot, &mut entry) };
+        while ok != FALSE {
+            let pid = entry.th32ProcessID;
+            if pid != 0 && session_id(pid) == own_session {
+                processes.push(ProcessInfo {
+                    pid,
+                    exe: image_path(pid),
+                });
+            }
+            ok = unsafe { Process32NextW(snapshot, &mut entry) };
+        }
+
+        unsafe { CloseHandle(snapshot) };
+        Ok(processes)
+    }
+
+    /// 通过主程序的退出事件请求其退出，让程序有机会保存设置与数据库；
+    /// 主程序的窗口关闭时只隐藏到托盘，因此 WM_CLOSE 只用于没有退出事件的进程
+    fn request_exit(&self, pid: u32) -> io::Result<()> {
+        if signal_exit_event(pid) {
+            return Ok(());
+        }
+
+        let mut context = CloseContext { pid, posted: 0 };
+        unsafe {
+            EnumWindows(
+                Some(post_close),
+                &mut context as *mut CloseContext as LPARAM,
+            )
+        };
+
+        if context.posted == 0 {
+            return Err(io::Error::other("进程没有可关闭的窗口"));
+        }
+        Ok(())
+    }
+
+    /// 只有进程 ID 不存在（`ERROR_INVALID_PARAMETER`）才视为已退出；
+    /// 权限不足等其他错误说明进程仍然存在，视为仍在运行，避免在程序运行时替换文件
+    fn is_running(&self, pid: u32) -> bool {
+        match ProcessHandle::open(pid, SYNCHRONIZE) {
+            Ok(handle) => unsafe { WaitForSingleObject(handle.0, 0) == WAIT_TIMEOUT },
+            Err(e) => e.raw_os_error() != Some(ERROR_INVALID_PARAMETER as i32),
+        }
+    }
+
+    fn kill(&self, pid: u32) -> io::Result<()> {
+        let handle = ProcessHandle::open(pid, PROCESS_TERMINATE)?;
+        if unsafe { TerminateProcess(handle.0, 1) } == FALSE {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// 设置主程序创建的退出事件，进程没有该事件（不是主程序或版本较旧）时返回 `false`
+fn signal_exit_event(pid: u32) -> bool {
+    let name = wide(format!("{}{}", EXIT_EVENT_PREFIX, pid));
+    let event = unsafe { OpenEventW(EVENT_MODIFY_STATE, FALSE, name.as_ptr()) };
+    if event.is_null() {
+        return false;
+    }
+    let signaled = unsafe { SetEvent(event) } != FALSE;
+    unsafe { CloseHandle(event) };
+    signaled
+}
+
+struct CloseContext {
+    pid: u32,
+    posted: usize,
+}
+
+unsafe extern "system" fn post_close(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let context = unsafe { &mut *(lparam as *mut CloseContext) };
+    let mut window_pid: DWORD = 0;
+    unsafe { GetWindowThreadProcessId(hwnd, &mut window_pid) };
+
+    if window_pid == context.pid && unsafe { PostMessageW(hwnd, WM_CLOSE, 0, 0) } != FALSE {
+        context.posted += 1;
+    }
+    TRUE
+}
+
+fn session_id(pid: u32) -> Option<DWORD> {
+    let mut session: DWORD = 0;
+    if unsafe { ProcessIdToSessionId(pid, &mut session) } == FALSE {
+        None
+    } else {
+        Some(session)
+    }
+}
+
+fn image_path(pid: u32) -> Option<PathBuf> {
+    let handle = ProcessHandle::open(pid, PROCESS_QUERY_LIMITED_INFORMATION).ok()?;
+    let mut buffer = vec![0u16; MAX_PATH * 4];
+    let mut size = buffer.len() as DWORD;
+
+    let ok = unsafe { QueryFullProcessImageNameW(handle.0, 0, buffer.as_mut_ptr(), &mut size) };
+    if ok == FALSE {
+        return None;
+    }
+    Some(PathBuf::from(OsString::from_wide(&buffer[..size as usize])))
+}
+
+/// 令牌句柄的 RAII 封装
+struct TokenHandle(HANDLE);
+
+impl Drop for TokenHandle {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+/// 当前进程是否以管理员身份（已提升）运行
+pub fn is_elevated() -> bool {
+    let mut token: HANDLE = ptr::null_mut();
+    if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == FALSE {
+        return false;
+    }
+    let token = TokenHandle(token);
+
+    let mut elevation: TOKEN_ELEVATION = unsafe { mem::zeroed() };
+    let mut size: DWORD = 0;
+    let ok = unsafe {
+        GetTokenInformation(
+            token.0,
+            TokenElevation,
+            &mut elevation as *mut TOKEN_ELEVATION as *mut _,
+            mem::size_of::<TOKEN_ELEVATION>() as DWORD,
+            &mut size,
+        )
+    };
+    ok != FALSE && elevation.TokenIsElevated != 0
+}
+
+/// 使用桌面 Shell（资源管理器）的令牌启动程序，使其以普通用户权限运行。
+///
+/// 当前进程已提升时才需要这样做；需要 `SeImpersonatePrivilege`，管理员进程默认具有该权限。
+pub fn spawn_unelevated(program: &Path, args: &[String], working_dir: &Path) -> io::Result<u32> {
+    let shell = unsafe { GetShellWindow() };
+    if shell.is_null() {
+        return Err(io::Error::other("找不到桌面 Shell 窗口"));
+    }
+    let mut shell_pid: DWORD = 0;
+    unsafe { GetWindowThreadProcessId(shell, &mut shell_pid) };
+    if shell_pid == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let shell_process = ProcessHandle::open(shell_pid, PROCESS_QUERY_INFORMATION)?;
+    let mut shell_token: HANDLE = ptr::null_mut();
+    if unsafe { OpenProcessToken(shell_process.0, TOKEN_DUPLICATE, &mut shell_token) } == FALSE {
+        return Err(io::Error::last_os_error());
+    }
+    let shell_token = TokenHandle(shell_token);
+
+    let mut primary: HANDLE = ptr::null_mut();
+    let access = TOKEN_QUERY
+        | TOKEN_ASSIGN_PRIMARY
+        | TOKEN_DUPLICATE
+        | TOKEN_ADJUST_DEFAULT
+        | TOKEN_ADJUST_SESSIONID;
+    let ok = unsafe {
+        DuplicateTokenEx(
+            shell_token.0,
+            access,
+            ptr::null_mut(),
+            SecurityImpersonation,
+            TokenPrimary,
+            &mut primary,
+        )
+    };
+    if ok == FALSE {
+        return Err(io::Error::last_os_error());
+    }
+    let primary = TokenHandle(primary);
+
+    let application = wide(program.as_os_str());
+    let mut command_line = wide(command_line(program, args));
+    let current_dir = wide(working_dir.as_os_str());
+    let mut startup: STARTUPINFOW = unsafe { mem::zeroed() };
+    startup.cb = mem::size_of::<STARTUPINFOW>() as DWORD;
+    let mut info: PROCESS_INFORMATION = unsafe { mem::zeroed() };
+
+    let ok = unsafe {
+        CreateProcessWithTokenW(
+            primary.0,
+            0,
+            application.as_ptr(),
+            command_line.as_mut_ptr(),
+            0,
+            ptr::null_mut(),
+            current_dir.as_ptr(),
+            &mut startup,
+            &mut info,
+        )
+    };
+    if ok == FALSE {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe {
+        CloseHandle(info.hThread);
+        CloseHandle(info.hProcess);
+    }
+    Ok(info.dwProcessId)
+}
+
+fn wide(value: impl AsRef<OsStr>) -> Vec<u16> {
+    value.as_ref().encode_wide().chain(Some(0)).collect()
+}
+
+/// 按 `CommandLineToArgvW` 的规则拼接命令行
+fn command_line(program: &Path, args: &[String]) -> OsString {
+    let mut line = OsString::from("\"");
+    line.push(program.as_os_str());
+    line.push("\"");
+    for arg in args {
+        line.push(" ");
+        line.push(quote_arg(arg));
+    }
+    line
+}
+
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '"']) {
+        return arg.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // 引号前的反斜杠需要加倍，再转义引号本身
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    // 结尾的反斜杠位于闭合引号之前，同样需要加倍
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_plain_arguments_unquoted() {
+        assert_eq!(quote_arg("--silent"), "--silent");
+        assert_eq!(
+            quote_arg(r"C:\Program\STranslate"),
+            r"C:\Program\STranslate"
+        );
+    }
+
+    #[test]
+    fn quotes_empty_and_spaced_arguments() {
+        assert_eq!(quote_arg(""), "\"\"");
+        assert_eq!(quote_arg("a b"), "\"a b\"");
+        assert_eq!(
+            quote_arg(r"C:\Program Files\STranslate\"),
+            r#""C:\Program Files\STranslate\\""#
+        );
+    }
+
+    #[test]
+    fn escapes_quotes_and_preceding_backslashes() {
+        assert_eq!(quote_arg(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_arg(r#"a\"b"#), r#""a\\\"b""#);
+    }
+
+    #[test]
+    fn builds_command_line() {
+        let line = command_line(
+            Path::new(r"C:\Program Files\STranslate\STranslate.exe"),
+            &["--tray".to_string(), "a b".to_string()],
+        );
+        assert_eq!(
+            line,
+            OsString::from(r#""C:\Program Files\STranslate\STranslate.exe" --tray "a b""#)
+        );
+    }
+}
@@ -30,6 +30,12 @@ public partial class PluginMetaData : ObservableObject
     /// </summary>
     public string Version { get; set; } = string.Empty;
 
+    /// <summary>
+    /// 插件编译时使用的插件接口版本（即引用的 STranslate.Plugin 版本），未声明时为空。
+    /// 更新程序据此判断用户插件是否与新版本兼容。
+    /// </summary>
+    public string ApiVersion { get; set; } = string.Empty;
+
     /// <summary>
     /// 插件描述信息。
     /// </summary>
@@ -131,6 +137,7 @@ public partial class PluginMetaData : ObservableObject
         Name = Name,
         Author = Author,
         Version = Version,
+        ApiVersion = ApiVersion,
         Description = Description,
         Website = Website,
         ExecuteFileName = ExecuteFileName,
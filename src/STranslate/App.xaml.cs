@@ -34,6 +34,8 @@ public partial class App : ISingleInstanceApp, INavigation, IDisposable
     private MainWindowViewModel? _mainWindowViewModel;
     private PluginManager? _pluginManager;
     private Notification? _notification;
+    private EventWaitHandle? _exitRequest;
+    private RegisteredWaitHandle? _exitRequestWait;
     private static bool _disposed;
 
     public bool IsNavigated { get; set; }
@@ -212,10 +214,12 @@ public partial class App : ISingleInstanceApp, INavigation, IDisposable
             _hotkeySettings?.LazyInitialize();
             UpdateToolTip();
             CheckAndShowInfo();
+            CheckUnfinishedUpdate();
             WebDavBackupOperation();
         };
 
         RegisterExitEvents();
+        RegisterExitRequest();
 
         _logger.LogInformation("End STranslate startup ----------------------------------------------------");
     }
@@ -257,6 +261,17 @@ public partial class App : ISingleInstanceApp, INavigation, IDisposable
         }
     }
 
+    private void CheckUnfinishedUpdate()
+    {
+        if (!File.Exists(DataLocation.UpdateJournalPath))
+            return;
+
+        _logger?.LogWarning($"Found unfinished update journal <{DataLocation.UpdateJournalPath}>, the installation may be inconsistent.");
+
+        var i18n = Ioc.Default.GetRequiredService<Internationalization>();
+        _notification?.Show(i18n.GetTranslation("UnfinishedUpdate"), i18n.GetTranslation("UnfinishedUpdateMessage"));
+    }
+
     private void WebDavBackupOperation()
     {
         if (!File.Exists(DataLocation.BackupFilePath))
@@ -313,6 +328,8 @@ public partial class App : ISingleInstanceApp, INavigation, IDisposable
             return;
         }
 
+        ApplyPendingUpdate();
+
         using var application = new App();
         application.InitializeComponent();
         application.Run();
@@ -344,6 +361,32 @@ public partial class App : ISingleInstanceApp, INavigation, IDisposable
         }
     }
 
+    /// <summary>
+    ///     上次更新时被占用的文件延迟到下次启动时替换，直接启动程序时在加载其余程序集之前完成
+    /// </summary>
+    private static void ApplyPendingUpdate()
+    {
+        if (!File.Exists(DataLocation.UpdatePendingPath) || !File.Exists(DataLocation.HostExePath))
+            return;
+
+        try
+        {
+            using var process = Process.Start(new ProcessStartInfo
+            {
+                FileName = DataLocation.HostExePath,
+                Arguments = "apply-pending",
+                WorkingDirectory = Constant.ProgramDirectory,
+                UseShellExecute = false,
+                CreateNoWindow = true
+            });
+            process?.WaitForExit(TimeSpan.FromSeconds(10));
+        }
+        catch (Exception ex)
+        {
+            Debug.WriteLine($"Cannot apply pending update: {ex.Message}");
+        }
+    }
+
     private static bool NeedAdmin()
     {
         var filePath = Path.Combine(DataLocation.SettingsDirectory, "Settings.json");
@@ -417,6 +460,26 @@ public partial class App : ISingleInstanceApp, INavigation, IDisposable
         };
     }
 
+    /// <summary>
+    ///     更新程序通过该事件请求正常退出（关闭主窗口只会隐藏到托盘），以便保存设置与数据库
+    /// </summary>
+    private void RegisterExitRequest()
+    {
+        try
+        {
+            _exitRequest = new EventWaitHandle(false, EventResetMode.ManualReset, Constant.ExitEventPrefix + Environment.ProcessId);
+            _exitRequestWait = ThreadPool.RegisterWaitForSingleObject(_exitRequest, (_, _) =>
+            {
+                _logger?.LogInformation("Exit requested by updater");
+                _ = Current.Dispatcher.InvokeAsync(() => Current.Shutdown());
+            }, null, Timeout.Infinite, true);
+        }
+        catch (Exception ex)
+        {
+            _logger?.LogWarning(ex, "Cannot create exit request event");
+        }
+    }
+
     [Conditional("RELEASE")]
     private void RegisterDispatcherUnhandledException()
     {
@@ -478,6 +541,8 @@ public partial class App : ISingleInstanceApp, INavigation, IDisposable
             // Dispose needs to be called on the main Windows thread,
             // since some resources owned by the thread need to be disposed.
             _notification?.Uninstall();
+            _exitRequestWait?.Unregister(null);
+            _exitRequest?.Dispose();
             _mainWindowViewModel?.Dispose();
             _mainWindow?.Dispatcher.Invoke(_mainWindow.Dispose);
             _pluginManager?.Dispose();
@@ -22,9 +22,18 @@ public class Constant
     public const string EmptyHotkey = "None";
     public const string PluginFileExtension = ".spkg";
     public const string NeedDelete = "NeedDelete.txt";
+    public const string NeedDisable = "NeedDisable.txt";
     public const string NeedUpgrade = "_NeedUpgrade";
     public const string InfoFileName = ".INFO";
     public const string BackupFileName = ".BACKUP";
+    public const string UpdateFolderName = ".update";
+    public const string UpdateJournalFileName = "journal.jsonl";
+    public const string UpdatePendingFolderName = ".update-pending";
+    public const string UpdatePendingFileName = "pending.json";
+    /// <summary>
+    ///     更新程序请求退出的命名事件前缀，后接进程 ID
+    /// </summary>
+    public const string ExitEventPrefix = @"Local\STranslate_Exit_";
 
     public const string Github = "https://github.com/STranslate/STranslate";
     public const string Sponsor = "https://github.com/STranslate/STranslate/tree/main?tab=readme-ov-file#donations";
@@ -12,6 +12,9 @@ public static class Helper
     public static bool ShouldDeleteDirectory(string directory)
         => File.Exists(Path.Combine(directory, Constant.NeedDelete));
 
+    public static bool ShouldSkipDirectory(string directory)
+        => File.Exists(Path.Combine(directory, Constant.NeedDisable));
+
     public static bool TryDeleteDirectory(string directory)
     {
         try
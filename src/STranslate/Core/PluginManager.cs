@@ -454,6 +454,13 @@ public class PluginManager : IDisposable
                 continue;
             }
 
+            // 更新程序停用的不兼容插件，保留目录，删除标记文件即可重新启用
+            if (Helper.ShouldSkipDirectory(tmp))
+            {
+                _logger.LogInformation($"Skipping disabled plugin directory: {tmp}");
+                continue;
+            }
+
             if (tmp.EndsWith(Constant.NeedUpgrade))
             {
                 _logger.LogDebug($"Upgrading plugin directory: {tmp}");